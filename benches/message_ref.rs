@@ -0,0 +1,56 @@
+//! Reading the paths of a message by decoding it into owned fields versus through a borrowed
+//! [EsMessageRef](endpointsecurity_rs::EsMessageRef).
+//!
+//! Both read the same copy of a simulated open event with a real payload, so only the decoding is
+//! measured and not the delivery of the message.
+
+use std::hint::black_box;
+use std::path::Path;
+use std::sync::Arc;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use endpointsecurity_rs::{EsActionType, EsClient, EsEventData, EsEventType, SimulatedBackend};
+
+fn bench_decode(c: &mut Criterion) {
+    let sim = Arc::new(SimulatedBackend::new());
+    let mut client = EsClient::with_backend(sim.clone()).unwrap();
+    client
+        .add_event(EsEventType::NotifyOpen)
+        .subscribe()
+        .unwrap();
+    let target = Path::new("/Users/foo/Library/Application Support/com.foo.app/state.sqlite");
+    sim.emit_file(EsActionType::Notify, EsEventType::NotifyOpen, target)
+        .unwrap();
+    let copy = client.recv_msg().unwrap().copy().unwrap();
+
+    let mut group = c.benchmark_group("decode paths");
+    group.bench_function("owned", |b| {
+        b.iter(|| {
+            let process = copy.process().unwrap();
+            let Some(EsEventData::NotifyOpen(open)) = copy.event_data() else {
+                unreachable!()
+            };
+            black_box((
+                process.pid,
+                process.exe.path,
+                process.signing_id,
+                open.file.path,
+            ))
+        })
+    });
+    group.bench_function("borrowed", |b| {
+        b.iter(|| {
+            let msg = copy.message_ref();
+            black_box((
+                msg.pid(),
+                msg.exe_path(),
+                msg.signing_id(),
+                msg.target_path(),
+            ))
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_decode);
+criterion_main!(benches);
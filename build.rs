@@ -1,6 +1,8 @@
 fn main() {
-    println!("cargo:rustc-link-lib=EndpointSecurity");
-    println!("cargo:rustc-link-lib=bsm");
+    // the system libraries only exist on macOS, everywhere else only the pure Rust parts are built
+    if std::env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("macos") {
+        println!("cargo:rustc-link-lib=EndpointSecurity");
+    }
 
     /*
     use std::path::PathBuf;
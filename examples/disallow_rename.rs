@@ -1,22 +1,27 @@
-use endpointsecurity_rs::{EsClient, EsEventData, EsEventType};
-
+#[cfg(target_os = "macos")]
 fn main() {
-    let mut client = EsClient::new().unwrap();
-    client.add_event(EsEventType::AuthRename).subscribe();
+    use endpointsecurity_rs::{EsClientBuilder, EsEventData, EsEventType};
+
+    let client = EsClientBuilder::new()
+        .event(EsEventType::AuthRename)
+        .build()
+        .unwrap();
 
     loop {
-        let ev = client.rx.recv().unwrap();
-        if let Some(ref data) = ev.event_data {
-            match data {
-                EsEventData::AuthRename(info) => {
-                    if info.source.path.contains("/Users/idipot/subcom.tech/test") {
-                        println!("{:?}", ev);
-                        ev.deny(&client);
-                    } else {
-                    }
+        let mut ev = client.recv_msg().unwrap();
+        if let Some(EsEventData::AuthRename(info)) = &ev.event_data {
+            if info.source.path.contains("/Users/idipot/subcom.tech/test") {
+                println!("{:?}", ev);
+                if let Some(auth) = ev.take_auth() {
+                    auth.deny().unwrap();
                 }
-                _ => {}
             }
         }
+        // other renames are allowed when `ev` is dropped
     }
 }
+
+#[cfg(not(target_os = "macos"))]
+fn main() {
+    eprintln!("Endpoint Security is only available on macOS");
+}
@@ -1,18 +1,22 @@
-use endpointsecurity_rs::{EsClient, EsEventData, EsEventType};
-
+#[cfg(target_os = "macos")]
 fn main() {
+    use endpointsecurity_rs::{EsClient, EsEventData, EsEventType};
+
     let mut client = EsClient::new().unwrap();
-    client.add_event(EsEventType::NotifyExec).subscribe();
+    client
+        .add_event(EsEventType::NotifyExec)
+        .subscribe()
+        .unwrap();
 
     loop {
         let msg = client.recv_msg().unwrap();
-        if let Some(ref data) = msg.event_data {
-            match data {
-                EsEventData::NotifyExec(proc) => {
-                    println!("{:?}", proc);
-                }
-                _ => {}
-            }
+        if let Some(EsEventData::NotifyExec(exec)) = &msg.event_data {
+            println!("{:?} {:?}", exec.target.exe.path, exec.args);
         }
     }
 }
+
+#[cfg(not(target_os = "macos"))]
+fn main() {
+    eprintln!("Endpoint Security is only available on macOS");
+}
@@ -1,14 +1,21 @@
-use endpointsecurity_rs::{EsClient, EsEventData, EsEventType};
-
+#[cfg(target_os = "macos")]
 fn main() {
+    use endpointsecurity_rs::{EsClient, EsEventType};
+
     let mut client = EsClient::new().unwrap();
     client
         .add_event(EsEventType::NotifyLWSessionLock)
         .add_event(EsEventType::NotifyLWSessionUnlock)
-        .subscribe();
+        .subscribe()
+        .unwrap();
 
     loop {
         let evt = client.recv_msg().unwrap();
         println!("{:?}", evt);
     }
 }
+
+#[cfg(not(target_os = "macos"))]
+fn main() {
+    eprintln!("Endpoint Security is only available on macOS");
+}
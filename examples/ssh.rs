@@ -1,29 +1,31 @@
-use endpointsecurity_rs::{EsAddressType, EsClient, EsEventData, EsEventType};
-
+#[cfg(target_os = "macos")]
 fn main() {
+    use endpointsecurity_rs::{EsAddressType, EsClient, EsEventData, EsEventType};
+
     let mut client = EsClient::new().unwrap();
     client
         .add_event(EsEventType::NotifyOpenSSHLogin)
-        .subscribe();
+        .subscribe()
+        .unwrap();
 
     loop {
-        let msg = client.rx.recv().unwrap();
-        if let Some(ref data) = msg.event_data {
-            match data {
-                EsEventData::NotifyOpenSSHLogin(ssh_deets) => {
-                    let addr = match &ssh_deets.source_address {
-                        EsAddressType::None => panic!("Sadge"),
-                        EsAddressType::Ipv4(addr) => addr.to_string(),
-                        EsAddressType::Ipv6(addr) => addr.to_string(),
-                        EsAddressType::NamedSocket(addr) => addr.clone(),
-                    };
-                    println!(
-                        "Someone from {} is trying to connect as {}",
-                        addr, ssh_deets.username
-                    );
-                }
-                _ => {}
-            }
+        let msg = client.recv_msg().unwrap();
+        if let Some(EsEventData::NotifyOpenSSHLogin(ssh_deets)) = &msg.event_data {
+            let addr = match &ssh_deets.source_address {
+                EsAddressType::None => panic!("Sadge"),
+                EsAddressType::Ipv4(addr) => addr.to_string(),
+                EsAddressType::Ipv6(addr) => addr.to_string(),
+                EsAddressType::NamedSocket(addr) => addr.clone(),
+            };
+            println!(
+                "Someone from {} is trying to connect as {}",
+                addr, ssh_deets.username
+            );
         }
     }
 }
+
+#[cfg(not(target_os = "macos"))]
+fn main() {
+    eprintln!("Endpoint Security is only available on macOS");
+}
@@ -0,0 +1,261 @@
+//! Responding to auth events.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Instant;
+
+use crate::backend::EsBackend;
+use crate::watchdog::Watchdog;
+use crate::{sys, EsError, EsEventType, EsOpenFlags};
+
+/// Verdict for an auth event
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum AuthDecision {
+    Allow,
+    Deny,
+}
+
+/// A retained auth message that hasn't been answered yet, shared between its [AuthRequest] and
+/// the [Watchdog].
+pub(crate) struct PendingAuth {
+    message_ptr: *const sys::es_message_t,
+    backend: Arc<dyn EsBackend>,
+    event: EsEventType,
+    seq_num: u64,
+    deadline: Instant,
+    answered: AtomicBool,
+}
+
+// SAFETY: see `EsMessage`, the message is only read and the answer is claimed atomically
+unsafe impl Send for PendingAuth {}
+unsafe impl Sync for PendingAuth {}
+
+impl PendingAuth {
+    pub(crate) fn event(&self) -> EsEventType {
+        self.event
+    }
+
+    pub(crate) fn seq_num(&self) -> u64 {
+        self.seq_num
+    }
+
+    pub(crate) fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    /// Returns true for the only caller allowed to answer the message
+    pub(crate) fn claim(&self) -> bool {
+        !self.answered.swap(true, Ordering::AcqRel)
+    }
+
+    /// Answer a claimed message. Events that only accept flags get all or none of them.
+    pub(crate) fn send(&self, decision: AuthDecision, cache: bool) -> Result<(), EsError> {
+        if self.event.accepts_flags_response() {
+            let flags = match decision {
+                AuthDecision::Allow => u32::MAX,
+                AuthDecision::Deny => 0,
+            };
+            return self.send_flags(flags, cache);
+        }
+
+        let result = match decision {
+            AuthDecision::Allow => sys::es_auth_result_t_ES_AUTH_RESULT_ALLOW,
+            AuthDecision::Deny => sys::es_auth_result_t_ES_AUTH_RESULT_DENY,
+        };
+        EsError::from_respond_result(self.backend.respond_auth_result(self.raw(), result, cache))
+    }
+
+    fn send_flags(&self, flags: u32, cache: bool) -> Result<(), EsError> {
+        EsError::from_respond_result(self.backend.respond_flags_result(self.raw(), flags, cache))
+    }
+
+    fn raw(&self) -> &sys::es_message_t {
+        // SAFETY: the message is retained for as long as `self` lives
+        unsafe { &*self.message_ptr }
+    }
+}
+
+impl Drop for PendingAuth {
+    fn drop(&mut self) {
+        self.backend.release_message(self.raw());
+    }
+}
+
+/// Auth related state of a client, shared with its message handler.
+pub(crate) struct AuthSettings {
+    default: Mutex<AuthDecision>,
+    watchdog: Mutex<Option<Arc<Watchdog>>>,
+    forced: Arc<AtomicU64>,
+}
+
+impl Default for AuthSettings {
+    fn default() -> Self {
+        Self {
+            default: Mutex::new(AuthDecision::Allow),
+            watchdog: Mutex::default(),
+            forced: Arc::default(),
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+impl AuthSettings {
+    pub(crate) fn set_default(&self, decision: AuthDecision) {
+        *lock(&self.default) = decision;
+    }
+
+    /// Counter of responses forced by any watchdog of the client
+    pub(crate) fn forced(&self) -> &Arc<AtomicU64> {
+        &self.forced
+    }
+
+    /// Replace the watchdog, returning the previous one
+    pub(crate) fn set_watchdog(&self, watchdog: Option<Arc<Watchdog>>) -> Option<Arc<Watchdog>> {
+        std::mem::replace(&mut *lock(&self.watchdog), watchdog)
+    }
+
+    /// Retain `message` for a response and hand it to the watchdog, if there is one
+    pub(crate) fn request(
+        &self,
+        message: &sys::es_message_t,
+        backend: Arc<dyn EsBackend>,
+        event: EsEventType,
+    ) -> AuthRequest {
+        backend.retain_message(message);
+
+        let pending = Arc::new(PendingAuth {
+            message_ptr: message,
+            deadline: backend.mach_time_to_instant(message.deadline),
+            seq_num: message.seq_num,
+            backend,
+            event,
+            answered: AtomicBool::new(false),
+        });
+        if let Some(watchdog) = lock(&self.watchdog).as_ref() {
+            watchdog.track(&pending);
+        }
+
+        AuthRequest {
+            pending,
+            default: *lock(&self.default),
+            cache: true,
+            consumed: false,
+        }
+    }
+}
+
+/// Pending response to an auth event, taken out of an [EsMessage](crate::EsMessage) with
+/// [EsMessage::take_auth](crate::EsMessage::take_auth).
+///
+/// The request is consumed by answering it, so it can't be answered twice. If it is dropped
+/// without an answer, the default decision is sent (uncached) so the kernel doesn't kill the
+/// client for missing the [deadline](Self::deadline).
+///
+/// Answering fails with [EsError::DuplicateResponse] if the client's
+/// [watchdog](crate::EsClient::start_auth_watchdog) already did.
+pub struct AuthRequest {
+    pending: Arc<PendingAuth>,
+    default: AuthDecision,
+    cache: bool,
+    consumed: bool,
+}
+
+impl std::fmt::Debug for AuthRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuthRequest")
+            .field("event", &self.pending.event)
+            .field("seq_num", &self.pending.seq_num)
+            .field("deadline", &self.pending.deadline)
+            .field("default", &self.default)
+            .field("cache", &self.cache)
+            .finish()
+    }
+}
+
+impl AuthRequest {
+    /// The event waiting for a response
+    pub fn event(&self) -> EsEventType {
+        self.pending.event
+    }
+
+    /// Sequence number of the message the request belongs to
+    pub fn seq_num(&self) -> u64 {
+        self.pending.seq_num
+    }
+
+    /// Point in time by which the request has to be answered, the kernel terminates clients that
+    /// miss it
+    pub fn deadline(&self) -> Instant {
+        self.pending.deadline
+    }
+
+    /// Decision sent if the request is dropped without an answer
+    pub fn default_decision(&self) -> AuthDecision {
+        self.default
+    }
+
+    /// Change the decision sent if the request is dropped without an answer
+    pub fn set_default_decision(&mut self, decision: AuthDecision) {
+        self.default = decision;
+    }
+
+    /// Whether the kernel caches the response, on by default. Cached results are reused for
+    /// matching events until [EsClient::clear_cache](crate::EsClient::clear_cache) is called.
+    ///
+    /// Default responses sent on drop are never cached.
+    pub fn cache(mut self, cache: bool) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    pub fn allow(self) -> Result<(), EsError> {
+        self.respond(AuthDecision::Allow)
+    }
+
+    pub fn deny(self) -> Result<(), EsError> {
+        self.respond(AuthDecision::Deny)
+    }
+
+    /// Authorize only `flags` out of the requested
+    /// [fflag](crate::EsOpen::fflag), e.g. allow reading but not writing a file.
+    ///
+    /// Returns [EsError::EventType] for events that don't
+    /// [accept flags](EsEventType::accepts_flags_response), the request is then answered with
+    /// its default decision.
+    pub fn respond_flags(mut self, flags: EsOpenFlags) -> Result<(), EsError> {
+        if !self.pending.event.accepts_flags_response() {
+            return Err(EsError::EventType);
+        }
+        self.claim()?;
+        self.pending.send_flags(flags.bits(), self.cache)
+    }
+
+    /// Answer with `decision`, events that [accept flags](EsEventType::accepts_flags_response)
+    /// are granted all or none of them
+    pub fn respond(mut self, decision: AuthDecision) -> Result<(), EsError> {
+        self.claim()?;
+        self.pending.send(decision, self.cache)
+    }
+
+    fn claim(&mut self) -> Result<(), EsError> {
+        self.consumed = true;
+        if self.pending.claim() {
+            Ok(())
+        } else {
+            Err(EsError::DuplicateResponse)
+        }
+    }
+}
+
+impl Drop for AuthRequest {
+    fn drop(&mut self) {
+        if !self.consumed && self.pending.claim() {
+            if let Err(err) = self.pending.send(self.default, false) {
+                println!("Failed to send default response: {}", err);
+            }
+        }
+    }
+}
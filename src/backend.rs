@@ -0,0 +1,141 @@
+//! The layer between [EsClient](crate::EsClient) and the Endpoint Security subsystem.
+//!
+//! [SystemBackend](crate::SystemBackend) talks to libEndpointSecurity on macOS,
+//! [SimulatedBackend](crate::SimulatedBackend) emulates it in-process so clients can be exercised
+//! without a signed, SIP-configured Mac, or on any other host.
+
+use std::ffi::{CStr, CString};
+use std::ptr::NonNull;
+use std::time::Instant;
+
+use crate::sys;
+
+/// Callback a backend invokes for every message it delivers to the client.
+pub type EsHandler = Box<dyn Fn(&sys::es_message_t) + Send + Sync>;
+
+/// Operations [EsClient](crate::EsClient) needs from the ES subsystem.
+///
+/// The methods mirror the C API one to one and return its raw status codes.
+pub trait EsBackend: Send + Sync {
+    /// Connect to the subsystem, `handler` receives every message delivered to the client.
+    fn new_client(&self, handler: EsHandler) -> sys::es_new_client_result_t;
+
+    /// Disconnect from the subsystem. No messages are delivered afterwards.
+    fn delete_client(&self) -> sys::es_return_t;
+
+    fn subscribe(&self, events: &[sys::es_event_type_t]) -> sys::es_return_t;
+
+    fn unsubscribe(&self, events: &[sys::es_event_type_t]) -> sys::es_return_t;
+
+    fn unsubscribe_all(&self) -> sys::es_return_t;
+
+    /// Events the client is subscribed to, `None` on error
+    fn subscriptions(&self) -> Option<Vec<sys::es_event_type_t>>;
+
+    fn respond_auth_result(
+        &self,
+        message: &sys::es_message_t,
+        result: sys::es_auth_result_t,
+        cache: bool,
+    ) -> sys::es_respond_result_t;
+
+    /// Respond to an auth event with the subset of `flags` that is authorized
+    fn respond_flags_result(
+        &self,
+        message: &sys::es_message_t,
+        flags: u32,
+        cache: bool,
+    ) -> sys::es_respond_result_t;
+
+    /// Convert a message `deadline` or `mach_time` to an [Instant]
+    fn mach_time_to_instant(&self, mach_time: u64) -> Instant;
+
+    /// Clear the auth results cached by the kernel
+    fn clear_cache(&self) -> sys::es_clear_cache_result_t;
+
+    fn mute_process(&self, audit_token: &sys::audit_token_t) -> sys::es_return_t;
+
+    fn mute_process_events(
+        &self,
+        audit_token: &sys::audit_token_t,
+        events: &[sys::es_event_type_t],
+    ) -> sys::es_return_t;
+
+    fn unmute_process(&self, audit_token: &sys::audit_token_t) -> sys::es_return_t;
+
+    fn unmute_process_events(
+        &self,
+        audit_token: &sys::audit_token_t,
+        events: &[sys::es_event_type_t],
+    ) -> sys::es_return_t;
+
+    /// Muted processes and the events muted for each, `None` on error
+    fn muted_processes_events(
+        &self,
+    ) -> Option<Vec<(sys::audit_token_t, Vec<sys::es_event_type_t>)>>;
+
+    fn mute_path(&self, path: &CStr, ty: sys::es_mute_path_type_t) -> sys::es_return_t;
+
+    fn mute_path_events(
+        &self,
+        path: &CStr,
+        ty: sys::es_mute_path_type_t,
+        events: &[sys::es_event_type_t],
+    ) -> sys::es_return_t;
+
+    fn unmute_path(&self, path: &CStr, ty: sys::es_mute_path_type_t) -> sys::es_return_t;
+
+    fn unmute_path_events(
+        &self,
+        path: &CStr,
+        ty: sys::es_mute_path_type_t,
+        events: &[sys::es_event_type_t],
+    ) -> sys::es_return_t;
+
+    /// Unmute all executable paths
+    fn unmute_all_paths(&self) -> sys::es_return_t;
+
+    /// Unmute all target paths
+    fn unmute_all_target_paths(&self) -> sys::es_return_t;
+
+    /// Muted paths with their type and muted events, `None` on error
+    #[allow(clippy::type_complexity)]
+    fn muted_paths_events(
+        &self,
+    ) -> Option<Vec<(CString, sys::es_mute_path_type_t, Vec<sys::es_event_type_t>)>>;
+
+    fn invert_muting(&self, ty: sys::es_mute_inversion_type_t) -> sys::es_return_t;
+
+    fn muting_inverted(&self, ty: sys::es_mute_inversion_type_t) -> sys::es_mute_inverted_return_t;
+
+    /// Arguments of an exec event, see `es_exec_arg`
+    fn exec_args(&self, event: &sys::es_event_exec_t) -> Vec<sys::es_string_token_t>;
+
+    /// Environment of an exec event, see `es_exec_env`
+    fn exec_envs(&self, event: &sys::es_event_exec_t) -> Vec<sys::es_string_token_t>;
+
+    /// File descriptors of an exec event, see `es_exec_fd`. Only valid in message version 4+.
+    fn exec_fds<'a>(&self, event: &'a sys::es_event_exec_t) -> Vec<&'a sys::es_fd_t>;
+
+    /// Number of entries of the ACL set by a setacl event, see `acl_get_entry`
+    fn acl_entry_count(&self, event: &sys::es_event_setacl_t) -> usize;
+
+    /// Text form of the ACL set by a setacl event, see `acl_to_text`
+    fn acl_text(&self, event: &sys::es_event_setacl_t) -> Option<String>;
+
+    /// Keep `message` alive after the handler returns
+    fn retain_message(&self, message: &sys::es_message_t);
+
+    /// Drop a reference taken with [Self::retain_message]
+    fn release_message(&self, message: &sys::es_message_t);
+
+    /// Copy `message` into an allocation independent of the original, see `es_copy_message`.
+    /// `None` on error.
+    fn copy_message(&self, message: &sys::es_message_t) -> Option<NonNull<sys::es_message_t>>;
+
+    /// Free a copy made with [Self::copy_message]
+    fn free_message(&self, message: &sys::es_message_t);
+
+    /// Size of `message` and the data it points to in bytes, see `es_message_size`
+    fn message_size(&self, message: &sys::es_message_t) -> usize;
+}
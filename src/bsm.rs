@@ -1,3 +1,92 @@
-extern "C" {
-    pub fn audit_token_to_pid(token: crate::sys::audit_token_t) -> i32;
+//! Audit tokens, decoded in pure Rust following libbsm's `audit_token_to_au32`.
+
+use crate::sys;
+
+/// Identity of a process instance.
+///
+/// Unlike a pid, the token also carries the pid version, so it stays unique when pids are reused
+/// and can be used as a map key for process identity.
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct AuditToken(pub [u32; 8]);
+
+impl AuditToken {
+    /// audit user id
+    pub fn auid(&self) -> u32 {
+        self.0[0]
+    }
+
+    /// effective user id
+    pub fn euid(&self) -> u32 {
+        self.0[1]
+    }
+
+    /// effective group id
+    pub fn egid(&self) -> u32 {
+        self.0[2]
+    }
+
+    /// real user id
+    pub fn ruid(&self) -> u32 {
+        self.0[3]
+    }
+
+    /// real group id
+    pub fn rgid(&self) -> u32 {
+        self.0[4]
+    }
+
+    pub fn pid(&self) -> i32 {
+        self.0[5] as i32
+    }
+
+    /// audit session id
+    pub fn asid(&self) -> i32 {
+        self.0[6] as i32
+    }
+
+    /// incremented every time the pid is reused
+    pub fn pidversion(&self) -> i32 {
+        self.0[7] as i32
+    }
+}
+
+impl From<sys::audit_token_t> for AuditToken {
+    fn from(token: sys::audit_token_t) -> Self {
+        Self(token.val)
+    }
+}
+
+impl From<AuditToken> for sys::audit_token_t {
+    fn from(token: AuditToken) -> Self {
+        Self { val: token.0 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AuditToken;
+
+    #[test]
+    fn test_audit_token_words() {
+        let token = AuditToken([501, 502, 20, 503, 21, 1234, 100007, 3]);
+        assert_eq!(token.auid(), 501);
+        assert_eq!(token.euid(), 502);
+        assert_eq!(token.egid(), 20);
+        assert_eq!(token.ruid(), 503);
+        assert_eq!(token.rgid(), 21);
+        assert_eq!(token.pid(), 1234);
+        assert_eq!(token.asid(), 100007);
+        assert_eq!(token.pidversion(), 3);
+    }
+
+    #[test]
+    fn test_reused_pid_is_a_different_process() {
+        let first = AuditToken([0, 0, 0, 0, 0, 1234, 1, 1]);
+        let reused = AuditToken([0, 0, 0, 0, 0, 1234, 1, 2]);
+        assert_eq!(first.pid(), reused.pid());
+        assert_ne!(first, reused);
+
+        let names: std::collections::HashMap<_, _> = [(first, "old"), (reused, "new")].into();
+        assert_eq!(names[&reused], "new");
+    }
 }
@@ -0,0 +1,336 @@
+//! Configuring a client in one place.
+
+use std::sync::Arc;
+
+use crate::channel::Channel;
+use crate::{
+    AuditToken, AuthDecision, EsBackend, EsClient, EsError, EsEventType, EsMessage, EsMessageRef,
+    EsMuteInversion, MessageCallback, MessageRefCallback, MuteRule, MuteSet, OverflowPolicy,
+};
+
+/// How messages reach the user of a client
+#[derive(Clone, Default)]
+#[non_exhaustive]
+pub enum HandlerMode {
+    /// Messages are queued and received with [EsClient::recv_msg] and friends
+    #[default]
+    Channel,
+    /// Messages are passed to the callback inside the ES handler and answered with its
+    /// decision, saving the trip through the channel. Keep it fast, the subsystem doesn't
+    /// deliver the next message before it returns.
+    Callback(MessageCallback),
+    /// Like [Self::Callback], passing a borrowed [EsMessageRef] that only decodes the fields
+    /// that are read
+    CallbackRef(MessageRefCallback),
+}
+
+impl std::fmt::Debug for HandlerMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HandlerMode::Channel => f.write_str("Channel"),
+            HandlerMode::Callback(_) => f.write_str("Callback"),
+            HandlerMode::CallbackRef(_) => f.write_str("CallbackRef"),
+        }
+    }
+}
+
+/// Builds a client that is subscribed and muted as configured.
+///
+/// Mutes and inversions are applied before subscribing, so no unwanted message is delivered
+/// in between.
+///
+/// # Example
+/// ```
+///     use std::sync::Arc;
+///     use endpointsecurity_rs::{
+///         AuthDecision, EsClientBuilder, EsEventType, EsMutePath, MuteRule, OverflowPolicy,
+///         SimulatedBackend,
+///     };
+///
+///     let client = EsClientBuilder::with_backend(Arc::new(SimulatedBackend::new()))
+///         .event(EsEventType::AuthOpen)
+///         .event(EsEventType::NotifyExit)
+///         .mute(MuteRule {
+///             path: "/usr/libexec".into(),
+///             ty: EsMutePath::Prefix,
+///             events: vec![],
+///         })
+///         .capacity(1024, OverflowPolicy::AllowAuth)
+///         .auth_default(AuthDecision::Deny)
+///         .build()
+///         .unwrap();
+///     assert_eq!(client.subscriptions().unwrap().len(), 2);
+/// ```
+pub struct EsClientBuilder {
+    backend: Arc<dyn EsBackend>,
+    events: Vec<EsEventType>,
+    mutes: MuteSet,
+    muted_processes: Vec<AuditToken>,
+    capacity: Option<(usize, OverflowPolicy)>,
+    auth_default: AuthDecision,
+    mode: HandlerMode,
+}
+
+#[cfg(target_os = "macos")]
+impl Default for EsClientBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EsClientBuilder {
+    /// Start configuring a client connected to the ES subsystem
+    #[cfg(target_os = "macos")]
+    pub fn new() -> Self {
+        Self::with_backend(Arc::new(crate::SystemBackend::default()))
+    }
+
+    /// Start configuring a client that receives its messages from `backend`
+    pub fn with_backend(backend: Arc<dyn EsBackend>) -> Self {
+        Self {
+            backend,
+            events: vec![],
+            mutes: MuteSet::default(),
+            muted_processes: vec![],
+            capacity: None,
+            auth_default: AuthDecision::Allow,
+            mode: HandlerMode::default(),
+        }
+    }
+
+    /// Subscribe to `event`
+    pub fn event(mut self, event: EsEventType) -> Self {
+        if !self.events.contains(&event) {
+            self.events.push(event);
+        }
+        self
+    }
+
+    /// Subscribe to all of `events`
+    pub fn events(self, events: impl IntoIterator<Item = EsEventType>) -> Self {
+        events.into_iter().fold(self, Self::event)
+    }
+
+    /// Mute a path, see [EsClient::mute_path_events]
+    pub fn mute(mut self, rule: MuteRule) -> Self {
+        self.mutes.rules.push(rule);
+        self
+    }
+
+    /// Mute the paths of `mutes` and apply its inversions, replacing the ones configured so far
+    pub fn mutes(mut self, mutes: MuteSet) -> Self {
+        self.mutes = mutes;
+        self
+    }
+
+    /// Mute all events of a process, see [EsClient::mute_process]
+    pub fn mute_process(mut self, audit_token: AuditToken) -> Self {
+        self.muted_processes.push(audit_token);
+        self
+    }
+
+    /// Invert muting for `ty`, see [EsClient::invert_muting]
+    pub fn invert_muting(mut self, ty: EsMuteInversion) -> Self {
+        if !self.mutes.inverted.contains(&ty) {
+            self.mutes.inverted.push(ty);
+        }
+        self
+    }
+
+    /// Queue at most `capacity` messages, see [EsClient::with_backend_bounded]. Unbounded if not
+    /// set. Only valid with [HandlerMode::Channel].
+    pub fn capacity(mut self, capacity: usize, policy: OverflowPolicy) -> Self {
+        self.capacity = Some((capacity, policy));
+        self
+    }
+
+    /// Decision for auth events that aren't answered, see [EsClient::set_auth_default]
+    pub fn auth_default(mut self, decision: AuthDecision) -> Self {
+        self.auth_default = decision;
+        self
+    }
+
+    /// How messages reach the user, [HandlerMode::Channel] by default
+    pub fn handler_mode(mut self, mode: HandlerMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Handle messages with `callback`, see [HandlerMode::Callback]
+    pub fn callback(
+        self,
+        callback: impl Fn(&EsMessage) -> Option<AuthDecision> + Send + Sync + 'static,
+    ) -> Self {
+        self.handler_mode(HandlerMode::Callback(Arc::new(callback)))
+    }
+
+    /// Handle borrowed messages with `callback`, see [HandlerMode::CallbackRef]
+    pub fn callback_ref(
+        self,
+        callback: impl Fn(EsMessageRef<'_>) -> Option<AuthDecision> + Send + Sync + 'static,
+    ) -> Self {
+        self.handler_mode(HandlerMode::CallbackRef(Arc::new(callback)))
+    }
+
+    /// Create the client, configure it and subscribe.
+    ///
+    /// Fails with [EsError::InvalidArgument] if no event was added, the capacity is 0 or a
+    /// capacity was set for one of the callback modes, which have no channel.
+    /// The client is deleted again if any step fails.
+    pub fn build(self) -> Result<EsClient, EsError> {
+        if self.events.is_empty() {
+            return Err(EsError::InvalidArgument);
+        }
+        if self.capacity.is_some() && !matches!(self.mode, HandlerMode::Channel) {
+            return Err(EsError::InvalidArgument);
+        }
+        let channel = match self.capacity {
+            Some((0, _)) => return Err(EsError::InvalidArgument),
+            Some((capacity, policy)) => Channel::bounded(capacity, policy),
+            None => Channel::unbounded(),
+        };
+
+        let mut client = match self.mode {
+            HandlerMode::Channel => EsClient::with_channel(self.backend, channel)?,
+            HandlerMode::Callback(callback) => EsClient::with_callback(self.backend, callback)?,
+            HandlerMode::CallbackRef(callback) => {
+                EsClient::with_callback_ref(self.backend, callback)?
+            }
+        };
+        client.set_auth_default(self.auth_default);
+
+        for audit_token in self.muted_processes {
+            client.mute_process(audit_token)?;
+        }
+        // without any mutes configured the default mutes of the system are kept as they are
+        if self.mutes != MuteSet::default() {
+            self.mutes.apply(&client)?;
+        }
+
+        for event in self.events {
+            client.add_event(event);
+        }
+        client.subscribe()?;
+
+        Ok(client)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{EsActionType, EsMutePath, EsMutedPath, SimResult, SimulatedBackend};
+
+    #[test]
+    fn test_build_configures_client() {
+        let sim = Arc::new(SimulatedBackend::new());
+        let client = EsClientBuilder::with_backend(sim.clone())
+            .events([EsEventType::AuthUnlink, EsEventType::NotifyExit])
+            .event(EsEventType::AuthUnlink)
+            .mute(MuteRule {
+                path: "/usr/libexec".into(),
+                ty: EsMutePath::Prefix,
+                events: vec![EsEventType::NotifyExit],
+            })
+            .invert_muting(EsMuteInversion::TargetPath)
+            .capacity(1, OverflowPolicy::DropNewest)
+            .auth_default(AuthDecision::Deny)
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            sim.subscriptions(),
+            [EsEventType::AuthUnlink, EsEventType::NotifyExit]
+        );
+        assert_eq!(
+            client.muted_paths_events().unwrap(),
+            [EsMutedPath {
+                path: PathBuf::from("/usr/libexec"),
+                ty: EsMutePath::Prefix,
+                events: vec![EsEventType::NotifyExit],
+            }]
+        );
+        assert_eq!(
+            client.muting_inverted(EsMuteInversion::TargetPath),
+            Ok(true)
+        );
+
+        sim.emit(EsActionType::Notify, EsEventType::NotifyExit);
+        sim.emit(EsActionType::Auth, EsEventType::AuthUnlink);
+        assert_eq!(client.channel_stats().dropped_newest, 1);
+        assert_eq!(sim.responses()[0].result, SimResult::Deny);
+    }
+
+    #[test]
+    fn test_build_keeps_default_mutes() {
+        let sim = Arc::new(SimulatedBackend::new());
+        let system = PathBuf::from("/System/Library/PrivateFrameworks");
+        sim.mute_path_by_default(&system, EsMutePath::Prefix);
+
+        let client = EsClientBuilder::with_backend(sim.clone())
+            .event(EsEventType::NotifyExit)
+            .build()
+            .unwrap();
+        assert_eq!(sim.muted_paths(), [(system.clone(), EsMutePath::Prefix)]);
+        drop(client);
+
+        // rules for other paths don't touch it either
+        let _client = EsClientBuilder::with_backend(sim.clone())
+            .event(EsEventType::NotifyExit)
+            .mute(MuteRule {
+                path: "/usr/libexec".into(),
+                ty: EsMutePath::Prefix,
+                events: vec![],
+            })
+            .build()
+            .unwrap();
+        assert_eq!(
+            sim.muted_paths(),
+            [
+                (system, EsMutePath::Prefix),
+                (PathBuf::from("/usr/libexec"), EsMutePath::Prefix)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_build_validates() {
+        let sim = Arc::new(SimulatedBackend::new());
+        let no_events = EsClientBuilder::with_backend(sim.clone()).build();
+        assert_eq!(no_events.err(), Some(EsError::InvalidArgument));
+
+        let no_capacity = EsClientBuilder::with_backend(sim.clone())
+            .event(EsEventType::NotifyExit)
+            .capacity(0, OverflowPolicy::Block)
+            .build();
+        assert_eq!(no_capacity.err(), Some(EsError::InvalidArgument));
+        assert!(!sim.is_connected());
+
+        let callback_capacity = EsClientBuilder::with_backend(sim.clone())
+            .event(EsEventType::NotifyExit)
+            .capacity(8, OverflowPolicy::DropOldestNotify)
+            .callback(|_| None)
+            .build();
+        assert_eq!(callback_capacity.err(), Some(EsError::InvalidArgument));
+        let callback_ref_capacity = EsClientBuilder::with_backend(sim.clone())
+            .event(EsEventType::NotifyExit)
+            .callback_ref(|_| None)
+            .capacity(8, OverflowPolicy::Block)
+            .build();
+        assert_eq!(callback_ref_capacity.err(), Some(EsError::InvalidArgument));
+        assert!(!sim.is_connected());
+
+        let bad_path = EsClientBuilder::with_backend(sim.clone())
+            .event(EsEventType::NotifyExit)
+            .mute(MuteRule {
+                path: "/tmp/\0".into(),
+                ty: EsMutePath::Literal,
+                events: vec![],
+            })
+            .build();
+        assert_eq!(bad_path.err(), Some(EsError::InvalidArgument));
+        assert!(!sim.is_connected());
+    }
+}
@@ -0,0 +1,142 @@
+//! Handling messages inline in the ES handler.
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+
+use crate::{AuthDecision, EsMessage, EsMessageRef};
+
+/// Called for every message of a client in [HandlerMode::Callback](crate::HandlerMode::Callback).
+///
+/// The returned decision answers auth events, `None` leaves them to the client's
+/// [default decision](crate::EsClient::set_auth_default). It is ignored for notify events.
+pub type MessageCallback = Arc<dyn Fn(&EsMessage) -> Option<AuthDecision> + Send + Sync>;
+
+/// Like [MessageCallback], for clients in
+/// [HandlerMode::CallbackRef](crate::HandlerMode::CallbackRef)
+pub type MessageRefCallback = Arc<dyn Fn(EsMessageRef<'_>) -> Option<AuthDecision> + Send + Sync>;
+
+fn run<T>(callback: impl FnOnce() -> Option<T>) -> Option<T> {
+    panic::catch_unwind(AssertUnwindSafe(callback)).unwrap_or_else(|_| {
+        println!("Message callback panicked, sending the default response");
+        None
+    })
+}
+
+/// Run `callback` for `message` and answer it. A panicking callback answers with the default
+/// decision instead of unwinding into the ES handler.
+pub(crate) fn dispatch(callback: &MessageCallback, mut message: EsMessage) {
+    let decision = run(|| callback(&message));
+
+    // without a decision the request answers with the default once `message` is dropped
+    if let (Some(decision), Some(auth)) = (decision, message.take_auth()) {
+        if let Err(err) = auth.respond(decision) {
+            println!("Failed to send response: {}", err);
+        }
+    }
+}
+
+/// Like [dispatch], the message is only retained for auth events. Their request is created before
+/// the callback runs so the watchdog tracks it while the callback is busy.
+pub(crate) fn dispatch_ref(callback: &MessageRefCallback, message: EsMessageRef<'_>) {
+    let auth = message.auth_request();
+    let decision = run(|| callback(message));
+
+    // dropping the request without a decision answers with the default
+    if let (Some(decision), Some(auth)) = (decision, auth) {
+        if let Err(err) = auth.respond(decision) {
+            println!("Failed to send response: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{EsActionType, EsClientBuilder, EsError, EsEventType, SimResult, SimulatedBackend};
+
+    #[test]
+    fn test_callback_answers_inline() {
+        let sim = Arc::new(SimulatedBackend::new());
+        let notified = Arc::new(AtomicUsize::new(0));
+        let callback_notified = notified.clone();
+        let client = EsClientBuilder::with_backend(sim.clone())
+            .events([EsEventType::AuthUnlink, EsEventType::AuthRename])
+            .event(EsEventType::NotifyExit)
+            .callback(move |msg| match msg.event {
+                EsEventType::AuthUnlink => Some(AuthDecision::Deny),
+                EsEventType::NotifyExit => {
+                    callback_notified.fetch_add(1, Ordering::SeqCst);
+                    Some(AuthDecision::Deny)
+                }
+                _ => None,
+            })
+            .build()
+            .unwrap();
+
+        sim.emit(EsActionType::Auth, EsEventType::AuthUnlink);
+        sim.emit(EsActionType::Auth, EsEventType::AuthRename);
+        sim.emit(EsActionType::Notify, EsEventType::NotifyExit);
+
+        // answered before `emit` returned
+        let responses = sim.responses();
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0].result, SimResult::Deny);
+        assert!(responses[0].cache);
+        assert_eq!(responses[1].result, SimResult::Allow);
+        assert!(!responses[1].cache);
+        assert_eq!(notified.load(Ordering::SeqCst), 1);
+
+        // nothing is queued in callback mode
+        assert_eq!(client.try_recv_msg().err(), Some(EsError::Disconnected));
+    }
+
+    #[test]
+    fn test_watchdog_tracks_slow_ref_callback() {
+        let sim = Arc::new(SimulatedBackend::new());
+        sim.set_deadline(Duration::from_millis(300));
+        let mut client = EsClientBuilder::with_backend(sim.clone())
+            .event(EsEventType::AuthUnlink)
+            .callback_ref(|_| {
+                std::thread::sleep(Duration::from_millis(500));
+                Some(AuthDecision::Allow)
+            })
+            .build()
+            .unwrap();
+        client
+            .start_auth_watchdog(Duration::from_millis(200), AuthDecision::Deny, None)
+            .unwrap();
+
+        sim.emit(EsActionType::Auth, EsEventType::AuthUnlink);
+
+        // answered by the watchdog while the callback was still running
+        let responses = sim.responses();
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses[0].result, SimResult::Deny);
+        assert_eq!(client.forced_auth_responses(), 1);
+        assert_eq!(sim.retained_messages(), 0);
+    }
+
+    #[test]
+    fn test_panic_sends_default() {
+        let sim = Arc::new(SimulatedBackend::new());
+        let _client = EsClientBuilder::with_backend(sim.clone())
+            .event(EsEventType::AuthUnlink)
+            .auth_default(AuthDecision::Deny)
+            .callback(|_| panic!("policy bug"))
+            .build()
+            .unwrap();
+
+        sim.emit(EsActionType::Auth, EsEventType::AuthUnlink);
+        sim.emit(EsActionType::Auth, EsEventType::AuthUnlink);
+
+        let responses = sim.responses();
+        assert_eq!(responses.len(), 2);
+        assert!(responses
+            .iter()
+            .all(|response| response.result == SimResult::Deny));
+        assert_eq!(sim.retained_messages(), 0);
+    }
+}
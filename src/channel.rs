@@ -0,0 +1,397 @@
+//! The channel messages travel through from the backend's handler to [EsClient](crate::EsClient).
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::task::Waker;
+
+use crate::{EsActionType, EsError, EsMessage};
+
+/// What happens to a message that arrives while a bounded channel is full
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum OverflowPolicy {
+    /// Hold the ES callback until the client makes room. Auth events keep ticking towards their
+    /// deadline meanwhile, and the subsystem doesn't deliver anything else to the client.
+    #[default]
+    Block,
+    /// Drop the arriving message, auth events are answered with the client's
+    /// [default decision](crate::EsClient::set_auth_default)
+    DropNewest,
+    /// Make room by dropping the oldest queued notify message. The arriving message is dropped
+    /// as with [Self::DropNewest] if only auth messages are queued.
+    DropOldestNotify,
+    /// Allow arriving auth events right away (uncached) and drop arriving notify messages
+    AllowAuth,
+}
+
+/// How many messages a bounded channel didn't deliver, see [EsClient::channel_stats](crate::EsClient::channel_stats)
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct ChannelStats {
+    /// arriving messages that were dropped
+    pub dropped_newest: u64,
+    /// queued notify messages dropped to make room
+    pub dropped_oldest: u64,
+    /// auth events allowed without reaching the client
+    pub auto_allowed: u64,
+    /// times the ES callback waited for room
+    pub blocked: u64,
+}
+
+#[derive(Default)]
+struct Counters {
+    dropped_newest: AtomicU64,
+    dropped_oldest: AtomicU64,
+    auto_allowed: AtomicU64,
+    blocked: AtomicU64,
+}
+
+#[derive(Default)]
+struct State {
+    queue: VecDeque<EsMessage>,
+    /// the handler is gone, no more messages arrive
+    disconnected: bool,
+    /// the client is gone, arriving messages are dropped
+    closed: bool,
+    /// tasks waiting in [EsClient::poll_recv](crate::EsClient::poll_recv)
+    wakers: Vec<Waker>,
+}
+
+/// Message queue shared by the handler and the client.
+pub(crate) struct Channel {
+    state: Mutex<State>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    /// `None` for an unbounded channel
+    capacity: Option<usize>,
+    policy: OverflowPolicy,
+    counters: Counters,
+}
+
+impl Channel {
+    pub(crate) fn unbounded() -> Arc<Self> {
+        Self::new(None, OverflowPolicy::default())
+    }
+
+    pub(crate) fn bounded(capacity: usize, policy: OverflowPolicy) -> Arc<Self> {
+        Self::new(Some(capacity), policy)
+    }
+
+    /// A channel nothing is ever sent on
+    pub(crate) fn disconnected() -> Arc<Self> {
+        let channel = Self::unbounded();
+        channel.disconnect();
+        channel
+    }
+
+    fn new(capacity: Option<usize>, policy: OverflowPolicy) -> Arc<Self> {
+        Arc::new(Self {
+            state: Mutex::default(),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+            capacity,
+            policy,
+            counters: Counters::default(),
+        })
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn is_full(&self, state: &State) -> bool {
+        self.capacity
+            .is_some_and(|capacity| state.queue.len() >= capacity)
+    }
+
+    /// Blocks until a message arrives or the handler is gone
+    pub(crate) fn recv(&self) -> Result<EsMessage, EsError> {
+        let mut state = self.state();
+        loop {
+            if let Some(message) = self.pop(&mut state) {
+                return Ok(message);
+            }
+            if state.disconnected {
+                return Err(EsError::Disconnected);
+            }
+            state = self
+                .not_empty
+                .wait(state)
+                .unwrap_or_else(|err| err.into_inner());
+        }
+    }
+
+    pub(crate) fn try_recv(&self) -> Result<Option<EsMessage>, EsError> {
+        self.try_recv_or_register(None)
+    }
+
+    /// Like [Self::try_recv], registering `waker` to be woken by the next message if there is none
+    pub(crate) fn try_recv_or_register(
+        &self,
+        waker: Option<&Waker>,
+    ) -> Result<Option<EsMessage>, EsError> {
+        let mut state = self.state();
+        if let Some(message) = self.pop(&mut state) {
+            return Ok(Some(message));
+        }
+        if state.disconnected {
+            return Err(EsError::Disconnected);
+        }
+        if let Some(waker) = waker {
+            if !state
+                .wakers
+                .iter()
+                .any(|registered| registered.will_wake(waker))
+            {
+                state.wakers.push(waker.clone());
+            }
+        }
+        Ok(None)
+    }
+
+    fn pop(&self, state: &mut State) -> Option<EsMessage> {
+        let message = state.queue.pop_front()?;
+        self.not_full.notify_one();
+        Some(message)
+    }
+
+    /// Drop messages arriving from now on and release a handler waiting for room
+    pub(crate) fn close(&self) {
+        self.state().closed = true;
+        self.not_full.notify_all();
+    }
+
+    pub(crate) fn stats(&self) -> ChannelStats {
+        let counters = &self.counters;
+        ChannelStats {
+            dropped_newest: counters.dropped_newest.load(Ordering::Relaxed),
+            dropped_oldest: counters.dropped_oldest.load(Ordering::Relaxed),
+            auto_allowed: counters.auto_allowed.load(Ordering::Relaxed),
+            blocked: counters.blocked.load(Ordering::Relaxed),
+        }
+    }
+
+    fn send(&self, mut message: EsMessage) {
+        let mut state = self.state();
+        let mut evicted = None;
+        if self.is_full(&state) && !state.closed {
+            match self.policy {
+                OverflowPolicy::Block => {
+                    self.counters.blocked.fetch_add(1, Ordering::Relaxed);
+                    while self.is_full(&state) && !state.closed {
+                        state = self
+                            .not_full
+                            .wait(state)
+                            .unwrap_or_else(|err| err.into_inner());
+                    }
+                }
+                OverflowPolicy::DropNewest => return self.drop_newest(state, message),
+                OverflowPolicy::DropOldestNotify => {
+                    let oldest = state
+                        .queue
+                        .iter()
+                        .position(|queued| queued.action == EsActionType::Notify);
+                    let Some(oldest) = oldest else {
+                        return self.drop_newest(state, message);
+                    };
+                    evicted = state.queue.remove(oldest);
+                    self.counters.dropped_oldest.fetch_add(1, Ordering::Relaxed);
+                }
+                OverflowPolicy::AllowAuth => {
+                    drop(state);
+                    match message.take_auth() {
+                        Some(auth) => {
+                            // the watchdog may have answered first, the event is allowed either way
+                            _ = auth.cache(false).allow();
+                            self.counters.auto_allowed.fetch_add(1, Ordering::Relaxed);
+                        }
+                        None => {
+                            self.counters.dropped_newest.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                    return;
+                }
+            }
+        }
+
+        if state.closed {
+            drop(state);
+            return;
+        }
+        state.queue.push_back(message);
+        let wakers = std::mem::take(&mut state.wakers);
+        drop(state);
+
+        self.not_empty.notify_one();
+        wakers.into_iter().for_each(Waker::wake);
+        // released outside the lock like any other dropped message
+        drop(evicted);
+    }
+
+    fn drop_newest(&self, state: MutexGuard<'_, State>, message: EsMessage) {
+        self.counters.dropped_newest.fetch_add(1, Ordering::Relaxed);
+        // dropping answers auth events, which must not happen under the lock
+        drop(state);
+        drop(message);
+    }
+
+    fn disconnect(&self) {
+        let mut state = self.state();
+        state.disconnected = true;
+        let wakers = std::mem::take(&mut state.wakers);
+        drop(state);
+
+        self.not_empty.notify_all();
+        wakers.into_iter().for_each(Waker::wake);
+    }
+}
+
+/// Sending half of the channel, owned by the handler. Disconnects the channel once it's dropped.
+pub(crate) struct MessageSender(Arc<Channel>);
+
+impl MessageSender {
+    pub(crate) fn new(channel: Arc<Channel>) -> Self {
+        Self(channel)
+    }
+
+    pub(crate) fn send(&self, message: EsMessage) {
+        self.0.send(message);
+    }
+}
+
+impl Drop for MessageSender {
+    fn drop(&mut self) {
+        self.0.disconnect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{AuthDecision, EsClient, EsEventType, SimResult, SimulatedBackend};
+
+    fn client(capacity: usize, policy: OverflowPolicy) -> (Arc<SimulatedBackend>, EsClient) {
+        let sim = Arc::new(SimulatedBackend::new());
+        let mut client = EsClient::with_backend_bounded(sim.clone(), capacity, policy).unwrap();
+        client
+            .add_event(EsEventType::NotifyOpen)
+            .add_event(EsEventType::AuthUnlink)
+            .subscribe()
+            .unwrap();
+        (sim, client)
+    }
+
+    fn notify(sim: &SimulatedBackend) -> u64 {
+        sim.emit(EsActionType::Notify, EsEventType::NotifyOpen)
+            .unwrap()
+    }
+
+    fn auth(sim: &SimulatedBackend) -> u64 {
+        sim.emit(EsActionType::Auth, EsEventType::AuthUnlink)
+            .unwrap()
+    }
+
+    fn drain(client: &EsClient) -> Vec<u64> {
+        std::iter::from_fn(|| client.try_recv_msg().unwrap())
+            .map(|msg| msg.seq_num)
+            .collect()
+    }
+
+    #[test]
+    fn test_zero_capacity_is_rejected() {
+        let sim = Arc::new(SimulatedBackend::new());
+        let client = EsClient::with_backend_bounded(sim, 0, OverflowPolicy::Block);
+        assert_eq!(client.err(), Some(EsError::InvalidArgument));
+    }
+
+    #[test]
+    fn test_drop_newest() {
+        let (sim, client) = client(2, OverflowPolicy::DropNewest);
+        let kept = [notify(&sim), notify(&sim)];
+        notify(&sim);
+        let allowed = auth(&sim);
+        client.set_auth_default(AuthDecision::Deny);
+        auth(&sim);
+
+        assert_eq!(drain(&client), kept);
+        assert_eq!(client.channel_stats().dropped_newest, 3);
+        // answered with the default decision at the time the message arrived
+        let responses = sim.responses();
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0].seq_num, allowed);
+        assert_eq!(responses[0].result, SimResult::Allow);
+        assert_eq!(responses[1].result, SimResult::Deny);
+    }
+
+    #[test]
+    fn test_drop_oldest_notify() {
+        let (sim, client) = client(3, OverflowPolicy::DropOldestNotify);
+        notify(&sim);
+        let pending = auth(&sim);
+        notify(&sim);
+        let third = notify(&sim);
+        let fourth = notify(&sim);
+        assert_eq!(drain(&client), [pending, third, fourth]);
+        assert_eq!(client.channel_stats().dropped_oldest, 2);
+
+        // nothing to evict, the arriving message goes
+        let auths = [auth(&sim), auth(&sim), auth(&sim)];
+        notify(&sim);
+        assert_eq!(drain(&client), auths);
+        assert_eq!(client.channel_stats().dropped_newest, 1);
+    }
+
+    #[test]
+    fn test_allow_auth() {
+        let (sim, client) = client(1, OverflowPolicy::AllowAuth);
+        client.set_auth_default(AuthDecision::Deny);
+        let kept = notify(&sim);
+        let allowed = auth(&sim);
+        notify(&sim);
+
+        assert_eq!(drain(&client), [kept]);
+        let stats = client.channel_stats();
+        assert_eq!(stats.auto_allowed, 1);
+        assert_eq!(stats.dropped_newest, 1);
+        let responses = sim.responses();
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses[0].seq_num, allowed);
+        assert_eq!(responses[0].result, SimResult::Allow);
+        assert!(!responses[0].cache);
+    }
+
+    #[test]
+    fn test_block_waits_for_room() {
+        let (sim, client) = client(1, OverflowPolicy::Block);
+        notify(&sim);
+
+        let emitter = {
+            let sim = sim.clone();
+            std::thread::spawn(move || notify(&sim))
+        };
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!emitter.is_finished());
+
+        assert_eq!(client.recv_msg().unwrap().seq_num, 0);
+        let blocked = emitter.join().unwrap();
+        assert_eq!(client.recv_msg().unwrap().seq_num, blocked);
+        assert_eq!(client.channel_stats().blocked, 1);
+    }
+
+    #[test]
+    fn test_drop_releases_blocked_handler() {
+        let (sim, client) = client(1, OverflowPolicy::Block);
+        notify(&sim);
+
+        let emitter = {
+            let sim = sim.clone();
+            std::thread::spawn(move || notify(&sim))
+        };
+        std::thread::sleep(Duration::from_millis(50));
+        drop(client);
+        emitter.join().unwrap();
+        assert_eq!(sim.retained_messages(), 0);
+    }
+}
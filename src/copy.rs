@@ -0,0 +1,206 @@
+//! Messages copied out of the ES subsystem.
+
+use std::ptr::NonNull;
+use std::sync::Arc;
+
+use crate::backend::EsBackend;
+use crate::{sys, EsActionType, EsError, EsEventData, EsEventType, EsMessageRef, EsProcess};
+
+/// A copy of a message made with `es_copy_message`, freed when dropped.
+///
+/// Unlike [EsMessage](crate::EsMessage) it doesn't hold on to the delivered message, so it can be
+/// kept in long-lived storage after the client is gone. It's `Send` and `Sync`. Copies can't be
+/// answered, respond to auth events through the original message.
+pub struct EsMessageCopy {
+    message: NonNull<sys::es_message_t>,
+    backend: Arc<dyn EsBackend>,
+}
+
+// SAFETY: the copy is owned exclusively, only read through `&self` and freed once in `Drop`
+unsafe impl Send for EsMessageCopy {}
+unsafe impl Sync for EsMessageCopy {}
+
+impl std::fmt::Debug for EsMessageCopy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EsMessageCopy")
+            .field("action", &self.action())
+            .field("event", &self.event())
+            .field("seq_num", &self.seq_num())
+            .field("size", &self.size())
+            .finish()
+    }
+}
+
+impl EsMessageCopy {
+    pub(crate) fn new(
+        message: &sys::es_message_t,
+        backend: Arc<dyn EsBackend>,
+    ) -> Result<Self, EsError> {
+        let message = backend.copy_message(message).ok_or(EsError::Failed)?;
+        Ok(Self { message, backend })
+    }
+
+    pub(crate) fn backend(&self) -> &Arc<dyn EsBackend> {
+        &self.backend
+    }
+
+    pub(crate) fn raw(&self) -> &sys::es_message_t {
+        // SAFETY: the copy lives until `self` is dropped
+        unsafe { self.message.as_ref() }
+    }
+
+    pub fn action(&self) -> EsActionType {
+        EsActionType::from_raw(self.raw().action_type)
+    }
+
+    pub fn event(&self) -> EsEventType {
+        EsEventType::from_raw(self.raw().event_type)
+    }
+
+    pub fn version(&self) -> u32 {
+        self.raw().version
+    }
+
+    pub fn seq_num(&self) -> u64 {
+        self.raw().seq_num
+    }
+
+    pub fn thread_id(&self) -> Option<u64> {
+        unsafe { self.raw().thread.as_ref() }.map(|thread| thread.thread_id)
+    }
+
+    /// Size of the copy in bytes, including the data it points to
+    pub fn size(&self) -> usize {
+        self.backend.message_size(self.raw())
+    }
+
+    /// Decode the process that caused the event
+    pub fn process(&self) -> Option<EsProcess> {
+        let raw = self.raw();
+        unsafe { raw.process.as_ref() }.map(|process| EsProcess::new(process, raw.version))
+    }
+
+    /// Decode the event payload
+    pub fn event_data(&self) -> Option<EsEventData> {
+        EsEventData::from_message(self.raw(), self.event(), self.backend.as_ref())
+    }
+
+    /// Borrowed view of the copy that decodes fields on demand
+    pub fn message_ref(&self) -> EsMessageRef<'_> {
+        EsMessageRef::from_copy(self.raw(), &self.backend)
+    }
+}
+
+impl Drop for EsMessageCopy {
+    fn drop(&mut self) {
+        self.backend.free_message(self.raw());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AuthRequest, EsClient, EsMessage, EsOpenFlags, SimulatedBackend};
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_send_sync() {
+        assert_send_sync::<EsMessage>();
+        assert_send_sync::<EsMessageCopy>();
+        assert_send_sync::<AuthRequest>();
+        assert_send_sync::<EsClient>();
+    }
+
+    #[test]
+    fn test_copy_outlives_client() {
+        let sim = Arc::new(SimulatedBackend::new());
+        let mut client = EsClient::with_backend(sim.clone()).unwrap();
+        client
+            .add_event(EsEventType::AuthUnlink)
+            .subscribe()
+            .unwrap();
+
+        let seq_num = sim.emit(EsActionType::Auth, EsEventType::AuthUnlink);
+        let msg = client.recv_msg().unwrap();
+        let copy = Arc::new(msg.copy().unwrap());
+        let pid = msg.process.as_ref().unwrap().pid;
+        drop(msg);
+        drop(client);
+        // the original is answered and released, the copy stays
+        assert_eq!(sim.responses().len(), 1);
+        assert_eq!(sim.retained_messages(), 0);
+        assert_eq!(sim.copied_messages(), 1);
+
+        let shared = copy.clone();
+        let (action, event, copied_seq_num, copied_pid) = std::thread::spawn(move || {
+            (
+                shared.action(),
+                shared.event(),
+                shared.seq_num(),
+                shared.process().unwrap().pid,
+            )
+        })
+        .join()
+        .unwrap();
+        assert_eq!(action, EsActionType::Auth);
+        assert_eq!(event, EsEventType::AuthUnlink);
+        assert_eq!(Some(copied_seq_num), seq_num);
+        assert_eq!(copied_pid, pid);
+        assert!(copy.size() >= std::mem::size_of::<sys::es_message_t>());
+
+        drop(copy);
+        assert_eq!(sim.copied_messages(), 0);
+    }
+
+    #[test]
+    fn test_copy_message_ref() {
+        let sim = Arc::new(SimulatedBackend::new());
+        let mut client = EsClient::with_backend(sim.clone()).unwrap();
+        client.add_event(EsEventType::AuthOpen).subscribe().unwrap();
+
+        let target = std::path::Path::new("/etc/hosts");
+        sim.emit_file(EsActionType::Auth, EsEventType::AuthOpen, target);
+        let mut msg = client.recv_msg().unwrap();
+        let copy = msg.copy().unwrap();
+        msg.take_auth()
+            .unwrap()
+            .respond_flags(EsOpenFlags::READ)
+            .unwrap();
+
+        let view = copy.message_ref();
+        assert_eq!(view.seq_num(), msg.seq_num);
+        assert_eq!(view.target_path(), Some(target));
+        assert_eq!(view.pid(), Some(msg.process.as_ref().unwrap().pid));
+        assert!(view.auth_request().is_none());
+    }
+
+    #[test]
+    fn test_copy_view_to_owned_copies_again() {
+        let sim = Arc::new(SimulatedBackend::new());
+        let mut client = EsClient::with_backend(sim.clone()).unwrap();
+        client
+            .add_event(EsEventType::NotifyOpen)
+            .subscribe()
+            .unwrap();
+
+        let target = std::path::Path::new("/etc/hosts");
+        sim.emit_file(EsActionType::Notify, EsEventType::NotifyOpen, target);
+        let copy = client.recv_msg().unwrap().copy().unwrap();
+        assert_eq!((sim.retained_messages(), sim.copied_messages()), (0, 1));
+
+        // the copy isn't retained, the owned message gets a copy of its own
+        let owned = copy.message_ref().to_owned().unwrap();
+        assert_eq!((sim.retained_messages(), sim.copied_messages()), (0, 2));
+        assert_eq!(owned.seq_num, copy.seq_num());
+        assert!(matches!(owned.event_data, Some(EsEventData::NotifyOpen(_))));
+
+        drop(copy);
+        let again = owned.copy().unwrap();
+        assert_eq!(again.message_ref().target_path(), Some(target));
+        drop(owned);
+        assert_eq!((sim.retained_messages(), sim.copied_messages()), (0, 1));
+        drop(again);
+        assert_eq!(sim.copied_messages(), 0);
+    }
+}
@@ -2,32 +2,73 @@
 //!
 //! This crate provides safe bindings to the the [OSX Endpoint Security API](https://developer.apple.com/documentation/endpointsecurity).
 
-//! This crates operators over crossbeam channels where you can subscribe to the events you're interested in.
+//! An [EsClient] talks to Endpoint Security through an [EsBackend]. You subscribe to the events
+//! you're interested in and receive them as [EsMessage]s over a channel, or handle them in a
+//! callback built with [EsClientBuilder].
+//!
+//! The `SystemBackend` and the FFI layer are only built on macOS. On other hosts the event types and
+//! their decoding are still available and an [EsClient] can be driven by a [SimulatedBackend], which
+//! is handy for testing.
 //!
 //! Not all events are supported. If you want a event to be added, open an issue on our [github](https://github.com/SubconsciousCompute/endpointsecurity-rs) repo.
 //!
 
-use std::ffi::CStr;
-
-use crossbeam::channel;
-
-use block::ConcreteBlock;
+use std::ffi::{CStr, CString};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Weak};
+use std::task::{Context, Poll};
+use std::thread::JoinHandle;
+use std::time::Duration;
 
 mod sys {
     #![allow(non_upper_case_globals)]
     #![allow(non_camel_case_types)]
     #![allow(non_snake_case)]
     #![allow(unused)]
+    #![allow(unknown_lints)]
+    #![allow(unnecessary_transmutes)]
     #![allow(clippy::all)]
     include!("./sys.rs");
 }
 
-#[allow(unused)]
+mod auth;
+mod backend;
 mod bsm;
+mod builder;
+mod callback;
+mod channel;
+mod copy;
+mod message_ref;
+mod mute;
+mod sim;
+mod stream;
+#[cfg(target_os = "macos")]
+mod system;
+mod watchdog;
+
+use auth::AuthSettings;
+pub use auth::{AuthDecision, AuthRequest};
+pub use backend::{EsBackend, EsHandler};
+pub use bsm::AuditToken;
+pub use builder::{EsClientBuilder, HandlerMode};
+pub use callback::{MessageCallback, MessageRefCallback};
+use channel::{Channel, MessageSender};
+pub use channel::{ChannelStats, OverflowPolicy};
+pub use copy::EsMessageCopy;
+pub use message_ref::EsMessageRef;
+pub use mute::{MuteApplyError, MuteChange, MuteRule, MuteSet};
+pub use sim::{SimResponse, SimResult, SimulatedBackend};
+#[cfg(feature = "async")]
+pub use stream::EsMessageStream;
+pub use stream::Recv;
+#[cfg(target_os = "macos")]
+pub use system::SystemBackend;
+pub use watchdog::{ForcedAuthCallback, ForcedAuthResponse};
 
 macro_rules! es_string_to_string {
     ($ex: expr) => {
-        CStr::from_ptr($ex).to_string_lossy().to_string()
+        es_string_to_opt_string!($ex).unwrap_or_default()
     };
 }
 
@@ -42,7 +83,7 @@ macro_rules! es_string_to_opt_string {
 }
 
 /// Possible errors returned if [EsClient::new()] fails
-#[derive(Debug)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum EsClientCreateError {
     /// Arguments to [EsClient] are invalid
     InvalidArgument = 1,
@@ -89,151 +130,364 @@ impl std::fmt::Display for EsClientCreateError {
 
 impl std::error::Error for EsClientCreateError {}
 
-/// All the events supported by Endpoint Security, see [more](https://developer.apple.com/documentation/endpointsecurity/event_types)
-///
-/// *README*: While all events are supported by the crate, only few have [EsEventData] types.
-/// If one of the event your interested in is missing, please send us a PR or open an issue on github.
+/// Errors returned by [EsClient] and by responses to [EsMessage]
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
-#[repr(u32)]
-pub enum EsEventType {
-    AuthExec,
-    AuthOpen,
-    AuthKExtLoad,
-    AuthMMap,
-    AuthMProtect,
-    AuthMount,
-    AuthRename,
-    AuthSignal,
-    AuthUnlink,
-    NotifyExec,
-    NotifyOpen,
-    NotifyFork,
-    NotifyClose,
-    NotifyCreate,
-    NotifyExchangeData,
-    NotifyExit,
-    NotifyGetTask,
-    NotifyKExtLoad,
-    NotifyKExtUnload,
-    NotifyLink,
-    NotifyMMap,
-    NotifyMProtect,
-    NotifyMount,
-    NotifyUnmount,
-    NotifyIOKitOpen,
-    NotifyRename,
-    NOtifySetAttrList,
-    NotifySetExtAttr,
-    NotifySetFlags,
-    NotifySetMode,
-    NotifySetOwner,
-    NotifySignal,
-    NotifyUnlink,
-    NotifyWrite,
-    AuthFileProviderMaterialize,
-    NotifyFileProviderMaterialize,
-    AuthFileProviderUpdate,
-    NotifyFileProviderUpdate,
-    AuthReadLink,
-    NotifyReadLink,
-    AuthTruncate,
-    NotifyTruncate,
-    AuthLink,
-    NotifyLookup,
-    AuthCreate,
-    AuthSetAttrList,
-    AuthSetExtAttr,
-    AuthSetFlags,
-    AuthSetMode,
-    AuthSetOwner,
-    AuthChdir,
-    NotifyChdir,
-    AuthGetAttrList,
-    NotifyGetAttrList,
-    NotifyStat,
-    NotifyAccess,
-    AuthChroot,
-    NotifyChroot,
-    AuthUtimes,
-    NotifyUtimes,
-    AuthClone,
-    NotifyClone,
-    NotifyFcntl,
-    AuthGetExtAttr,
-    NotifyGetExtAttr,
-    AuthListenExtAttr,
-    NotifyListenExtAttr,
-    AuthReadDir,
-    NotifyReadDir,
-    AuthDeleteExtAttr,
-    NotifyDeleteExtAttr,
-    AuthFsGetPath,
-    NotifyFsGetPath,
-    NotifyDup,
-    AuthSetTime,
-    NotifySetTime,
-    NotifyUIPCBind,
-    AuthUIPCBind,
-    NotifyUIPCConnect,
-    AuthUIPCConnect,
-    AuthExchangeData,
-    AuthSetACL,
-    NotifySetACL,
-    NotifyPTYGrant,
-    NotifyPTYClose,
-    AuthProcCheck,
-    NotifyProcCheck,
-    AuthGetTask,
-    AuthSearchFs,
-    NotifySearchFs,
-    AuthFcntl,
-    AuthIOKitOpen,
-    AuthProcSuspendResume,
-    NotifyProcSuspendResume,
-    NotifyCsInvalidDate,
-    NotifyGetTaskName,
-    NotfiyTrace,
-    NotifyRemoteThreadCreate,
-    AuthRemount,
-    NotifyRemount,
-    AuthGetTaskRead,
-    NotifyGetTaskRead,
-    NotifyGetTaskInspect,
-    NotifySetUid,
-    NotifySetGid,
-    NotifySetEUid,
-    NotifySetEGuid,
-    NotifySetREUid,
-    NotifySetREGuid,
-    AuthCopyFile,
-    NotifyCopyFile,
-    NotifyAuthentication,
-    NotifyXPMalwareDetected,
-    NotifyXPMalwareRemediated,
-    NotifyLWSessionLogin,
-    NotifyLWSessionLogout,
-    NotifyLWSessionLock,
-    NotifyLWSessionUnlock,
-    NotifyScreenSharingAttach,
-    AuthScreenSharingAttach,
-    NotifyOpenSSHLogin,
-    NotifyOpenSSHLogout,
-    NotifyBTMLaunchItemAdd,
-    NotifyBTMLaunchItemRemove,
-    Last,
+pub enum EsError {
+    /// The client couldn't be created
+    Create(EsClientCreateError),
+    /// The ES subsystem reported a failure (`ES_RETURN_ERROR`)
+    Failed,
+    /// Arguments of the call were invalid, e.g. responding to a notify event
+    InvalidArgument,
+    /// Communication with the ES subsystem failed
+    Internal,
+    /// The message to respond to couldn't be found
+    NotFound,
+    /// The message was already responded to
+    DuplicateResponse,
+    /// The kind of response doesn't match the event type
+    EventType,
+    /// Cache clearing is being throttled, retry later
+    Throttled,
+    /// The client isn't subscribed to the event
+    NotSubscribed(EsEventType),
+    /// All senders of the message channel are gone
+    Disconnected,
+    /// The raw value isn't a known event type
+    UnknownEvent(u32),
+    /// A thread needed by the client couldn't be started
+    ThreadSpawn,
+}
+
+impl EsError {
+    fn from_return(code: sys::es_return_t) -> Result<(), EsError> {
+        match code {
+            sys::es_return_t_ES_RETURN_SUCCESS => Ok(()),
+            _ => Err(EsError::Failed),
+        }
+    }
+
+    fn from_respond_result(code: sys::es_respond_result_t) -> Result<(), EsError> {
+        match code {
+            sys::es_respond_result_t_ES_RESPOND_RESULT_SUCCESS => Ok(()),
+            sys::es_respond_result_t_ES_RESPOND_RESULT_ERR_INVALID_ARGUMENT => {
+                Err(EsError::InvalidArgument)
+            }
+            sys::es_respond_result_t_ES_RESPOND_RESULT_NOT_FOUND => Err(EsError::NotFound),
+            sys::es_respond_result_t_ES_RESPOND_RESULT_ERR_DUPLICATE_RESPONSE => {
+                Err(EsError::DuplicateResponse)
+            }
+            sys::es_respond_result_t_ES_RESPOND_RESULT_ERR_EVENT_TYPE => Err(EsError::EventType),
+            _ => Err(EsError::Internal),
+        }
+    }
+
+    fn from_clear_cache_result(code: sys::es_clear_cache_result_t) -> Result<(), EsError> {
+        match code {
+            sys::es_clear_cache_result_t_ES_CLEAR_CACHE_RESULT_SUCCESS => Ok(()),
+            sys::es_clear_cache_result_t_ES_CLEAR_CACHE_RESULT_ERR_THROTTLE => {
+                Err(EsError::Throttled)
+            }
+            _ => Err(EsError::Internal),
+        }
+    }
+}
+
+impl From<EsClientCreateError> for EsError {
+    fn from(err: EsClientCreateError) -> Self {
+        EsError::Create(err)
+    }
+}
+
+impl std::fmt::Display for EsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EsError::Create(err) => err.fmt(f),
+            EsError::Failed => f.write_str("Error: ES subsystem reported a failure"),
+            EsError::InvalidArgument => f.write_str("Error: Invalid Arguments were provided."),
+            EsError::Internal => f.write_str("Error: Communication with ES subsystem failed."),
+            EsError::NotFound => f.write_str("Error: Message not found"),
+            EsError::DuplicateResponse => f.write_str("Error: Message was already responded to"),
+            EsError::EventType => f.write_str("Error: Response type doesn't match the event"),
+            EsError::Throttled => f.write_str("Error: Request is being throttled"),
+            EsError::NotSubscribed(event) => write!(f, "Error: Not subscribed to {:?}", event),
+            EsError::Disconnected => f.write_str("Error: Message channel is disconnected"),
+            EsError::UnknownEvent(raw) => write!(f, "Error: Unknown event type {}", raw),
+            EsError::ThreadSpawn => f.write_str("Error: Failed to start a thread"),
+        }
+    }
+}
+
+impl std::error::Error for EsError {}
+
+macro_rules! es_event_types {
+    ($(#[$meta: meta])* $($name: ident => $raw: ident,)*) => {
+        $(#[$meta])*
+        pub enum EsEventType {
+            $($name,)*
+            /// An event this crate doesn't know about, e.g. one added in a newer macOS release.
+            /// Never holds the raw value of a known event.
+            Unknown(u32),
+        }
+
+        impl EsEventType {
+            /// Convert a raw `es_event_type_t`, values without a variant become [EsEventType::Unknown]
+            pub fn from_raw(raw: u32) -> Self {
+                match raw {
+                    $(sys::$raw => EsEventType::$name,)*
+                    _ => EsEventType::Unknown(raw),
+                }
+            }
+
+            /// The raw `es_event_type_t` value of the event
+            pub fn raw(self) -> u32 {
+                match self {
+                    $(EsEventType::$name => sys::$raw,)*
+                    EsEventType::Unknown(raw) => raw,
+                }
+            }
+        }
+    };
+}
+
+es_event_types! {
+    /// All the events supported by Endpoint Security, see [more](https://developer.apple.com/documentation/endpointsecurity/event_types)
+    ///
+    /// *README*: While all events are supported by the crate, only few have [EsEventData] types.
+    /// If one of the event your interested in is missing, please send us a PR or open an issue on github.
+    #[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+    #[cfg_attr(feature = "config", derive(serde::Deserialize, serde::Serialize))]
+    AuthExec => es_event_type_t_ES_EVENT_TYPE_AUTH_EXEC,
+    AuthOpen => es_event_type_t_ES_EVENT_TYPE_AUTH_OPEN,
+    AuthKExtLoad => es_event_type_t_ES_EVENT_TYPE_AUTH_KEXTLOAD,
+    AuthMMap => es_event_type_t_ES_EVENT_TYPE_AUTH_MMAP,
+    AuthMProtect => es_event_type_t_ES_EVENT_TYPE_AUTH_MPROTECT,
+    AuthMount => es_event_type_t_ES_EVENT_TYPE_AUTH_MOUNT,
+    AuthRename => es_event_type_t_ES_EVENT_TYPE_AUTH_RENAME,
+    AuthSignal => es_event_type_t_ES_EVENT_TYPE_AUTH_SIGNAL,
+    AuthUnlink => es_event_type_t_ES_EVENT_TYPE_AUTH_UNLINK,
+    NotifyExec => es_event_type_t_ES_EVENT_TYPE_NOTIFY_EXEC,
+    NotifyOpen => es_event_type_t_ES_EVENT_TYPE_NOTIFY_OPEN,
+    NotifyFork => es_event_type_t_ES_EVENT_TYPE_NOTIFY_FORK,
+    NotifyClose => es_event_type_t_ES_EVENT_TYPE_NOTIFY_CLOSE,
+    NotifyCreate => es_event_type_t_ES_EVENT_TYPE_NOTIFY_CREATE,
+    NotifyExchangeData => es_event_type_t_ES_EVENT_TYPE_NOTIFY_EXCHANGEDATA,
+    NotifyExit => es_event_type_t_ES_EVENT_TYPE_NOTIFY_EXIT,
+    NotifyGetTask => es_event_type_t_ES_EVENT_TYPE_NOTIFY_GET_TASK,
+    NotifyKExtLoad => es_event_type_t_ES_EVENT_TYPE_NOTIFY_KEXTLOAD,
+    NotifyKExtUnload => es_event_type_t_ES_EVENT_TYPE_NOTIFY_KEXTUNLOAD,
+    NotifyLink => es_event_type_t_ES_EVENT_TYPE_NOTIFY_LINK,
+    NotifyMMap => es_event_type_t_ES_EVENT_TYPE_NOTIFY_MMAP,
+    NotifyMProtect => es_event_type_t_ES_EVENT_TYPE_NOTIFY_MPROTECT,
+    NotifyMount => es_event_type_t_ES_EVENT_TYPE_NOTIFY_MOUNT,
+    NotifyUnmount => es_event_type_t_ES_EVENT_TYPE_NOTIFY_UNMOUNT,
+    NotifyIOKitOpen => es_event_type_t_ES_EVENT_TYPE_NOTIFY_IOKIT_OPEN,
+    NotifyRename => es_event_type_t_ES_EVENT_TYPE_NOTIFY_RENAME,
+    NOtifySetAttrList => es_event_type_t_ES_EVENT_TYPE_NOTIFY_SETATTRLIST,
+    NotifySetExtAttr => es_event_type_t_ES_EVENT_TYPE_NOTIFY_SETEXTATTR,
+    NotifySetFlags => es_event_type_t_ES_EVENT_TYPE_NOTIFY_SETFLAGS,
+    NotifySetMode => es_event_type_t_ES_EVENT_TYPE_NOTIFY_SETMODE,
+    NotifySetOwner => es_event_type_t_ES_EVENT_TYPE_NOTIFY_SETOWNER,
+    NotifySignal => es_event_type_t_ES_EVENT_TYPE_NOTIFY_SIGNAL,
+    NotifyUnlink => es_event_type_t_ES_EVENT_TYPE_NOTIFY_UNLINK,
+    NotifyWrite => es_event_type_t_ES_EVENT_TYPE_NOTIFY_WRITE,
+    AuthFileProviderMaterialize => es_event_type_t_ES_EVENT_TYPE_AUTH_FILE_PROVIDER_MATERIALIZE,
+    NotifyFileProviderMaterialize => es_event_type_t_ES_EVENT_TYPE_NOTIFY_FILE_PROVIDER_MATERIALIZE,
+    AuthFileProviderUpdate => es_event_type_t_ES_EVENT_TYPE_AUTH_FILE_PROVIDER_UPDATE,
+    NotifyFileProviderUpdate => es_event_type_t_ES_EVENT_TYPE_NOTIFY_FILE_PROVIDER_UPDATE,
+    AuthReadLink => es_event_type_t_ES_EVENT_TYPE_AUTH_READLINK,
+    NotifyReadLink => es_event_type_t_ES_EVENT_TYPE_NOTIFY_READLINK,
+    AuthTruncate => es_event_type_t_ES_EVENT_TYPE_AUTH_TRUNCATE,
+    NotifyTruncate => es_event_type_t_ES_EVENT_TYPE_NOTIFY_TRUNCATE,
+    AuthLink => es_event_type_t_ES_EVENT_TYPE_AUTH_LINK,
+    NotifyLookup => es_event_type_t_ES_EVENT_TYPE_NOTIFY_LOOKUP,
+    AuthCreate => es_event_type_t_ES_EVENT_TYPE_AUTH_CREATE,
+    AuthSetAttrList => es_event_type_t_ES_EVENT_TYPE_AUTH_SETATTRLIST,
+    AuthSetExtAttr => es_event_type_t_ES_EVENT_TYPE_AUTH_SETEXTATTR,
+    AuthSetFlags => es_event_type_t_ES_EVENT_TYPE_AUTH_SETFLAGS,
+    AuthSetMode => es_event_type_t_ES_EVENT_TYPE_AUTH_SETMODE,
+    AuthSetOwner => es_event_type_t_ES_EVENT_TYPE_AUTH_SETOWNER,
+    AuthChdir => es_event_type_t_ES_EVENT_TYPE_AUTH_CHDIR,
+    NotifyChdir => es_event_type_t_ES_EVENT_TYPE_NOTIFY_CHDIR,
+    AuthGetAttrList => es_event_type_t_ES_EVENT_TYPE_AUTH_GETATTRLIST,
+    NotifyGetAttrList => es_event_type_t_ES_EVENT_TYPE_NOTIFY_GETATTRLIST,
+    NotifyStat => es_event_type_t_ES_EVENT_TYPE_NOTIFY_STAT,
+    NotifyAccess => es_event_type_t_ES_EVENT_TYPE_NOTIFY_ACCESS,
+    AuthChroot => es_event_type_t_ES_EVENT_TYPE_AUTH_CHROOT,
+    NotifyChroot => es_event_type_t_ES_EVENT_TYPE_NOTIFY_CHROOT,
+    AuthUtimes => es_event_type_t_ES_EVENT_TYPE_AUTH_UTIMES,
+    NotifyUtimes => es_event_type_t_ES_EVENT_TYPE_NOTIFY_UTIMES,
+    AuthClone => es_event_type_t_ES_EVENT_TYPE_AUTH_CLONE,
+    NotifyClone => es_event_type_t_ES_EVENT_TYPE_NOTIFY_CLONE,
+    NotifyFcntl => es_event_type_t_ES_EVENT_TYPE_NOTIFY_FCNTL,
+    AuthGetExtAttr => es_event_type_t_ES_EVENT_TYPE_AUTH_GETEXTATTR,
+    NotifyGetExtAttr => es_event_type_t_ES_EVENT_TYPE_NOTIFY_GETEXTATTR,
+    AuthListenExtAttr => es_event_type_t_ES_EVENT_TYPE_AUTH_LISTEXTATTR,
+    NotifyListenExtAttr => es_event_type_t_ES_EVENT_TYPE_NOTIFY_LISTEXTATTR,
+    AuthReadDir => es_event_type_t_ES_EVENT_TYPE_AUTH_READDIR,
+    NotifyReadDir => es_event_type_t_ES_EVENT_TYPE_NOTIFY_READDIR,
+    AuthDeleteExtAttr => es_event_type_t_ES_EVENT_TYPE_AUTH_DELETEEXTATTR,
+    NotifyDeleteExtAttr => es_event_type_t_ES_EVENT_TYPE_NOTIFY_DELETEEXTATTR,
+    AuthFsGetPath => es_event_type_t_ES_EVENT_TYPE_AUTH_FSGETPATH,
+    NotifyFsGetPath => es_event_type_t_ES_EVENT_TYPE_NOTIFY_FSGETPATH,
+    NotifyDup => es_event_type_t_ES_EVENT_TYPE_NOTIFY_DUP,
+    AuthSetTime => es_event_type_t_ES_EVENT_TYPE_AUTH_SETTIME,
+    NotifySetTime => es_event_type_t_ES_EVENT_TYPE_NOTIFY_SETTIME,
+    NotifyUIPCBind => es_event_type_t_ES_EVENT_TYPE_NOTIFY_UIPC_BIND,
+    AuthUIPCBind => es_event_type_t_ES_EVENT_TYPE_AUTH_UIPC_BIND,
+    NotifyUIPCConnect => es_event_type_t_ES_EVENT_TYPE_NOTIFY_UIPC_CONNECT,
+    AuthUIPCConnect => es_event_type_t_ES_EVENT_TYPE_AUTH_UIPC_CONNECT,
+    AuthExchangeData => es_event_type_t_ES_EVENT_TYPE_AUTH_EXCHANGEDATA,
+    AuthSetACL => es_event_type_t_ES_EVENT_TYPE_AUTH_SETACL,
+    NotifySetACL => es_event_type_t_ES_EVENT_TYPE_NOTIFY_SETACL,
+    NotifyPTYGrant => es_event_type_t_ES_EVENT_TYPE_NOTIFY_PTY_GRANT,
+    NotifyPTYClose => es_event_type_t_ES_EVENT_TYPE_NOTIFY_PTY_CLOSE,
+    AuthProcCheck => es_event_type_t_ES_EVENT_TYPE_AUTH_PROC_CHECK,
+    NotifyProcCheck => es_event_type_t_ES_EVENT_TYPE_NOTIFY_PROC_CHECK,
+    AuthGetTask => es_event_type_t_ES_EVENT_TYPE_AUTH_GET_TASK,
+    AuthSearchFs => es_event_type_t_ES_EVENT_TYPE_AUTH_SEARCHFS,
+    NotifySearchFs => es_event_type_t_ES_EVENT_TYPE_NOTIFY_SEARCHFS,
+    AuthFcntl => es_event_type_t_ES_EVENT_TYPE_AUTH_FCNTL,
+    AuthIOKitOpen => es_event_type_t_ES_EVENT_TYPE_AUTH_IOKIT_OPEN,
+    AuthProcSuspendResume => es_event_type_t_ES_EVENT_TYPE_AUTH_PROC_SUSPEND_RESUME,
+    NotifyProcSuspendResume => es_event_type_t_ES_EVENT_TYPE_NOTIFY_PROC_SUSPEND_RESUME,
+    NotifyCsInvalidDate => es_event_type_t_ES_EVENT_TYPE_NOTIFY_CS_INVALIDATED,
+    NotifyGetTaskName => es_event_type_t_ES_EVENT_TYPE_NOTIFY_GET_TASK_NAME,
+    NotfiyTrace => es_event_type_t_ES_EVENT_TYPE_NOTIFY_TRACE,
+    NotifyRemoteThreadCreate => es_event_type_t_ES_EVENT_TYPE_NOTIFY_REMOTE_THREAD_CREATE,
+    AuthRemount => es_event_type_t_ES_EVENT_TYPE_AUTH_REMOUNT,
+    NotifyRemount => es_event_type_t_ES_EVENT_TYPE_NOTIFY_REMOUNT,
+    AuthGetTaskRead => es_event_type_t_ES_EVENT_TYPE_AUTH_GET_TASK_READ,
+    NotifyGetTaskRead => es_event_type_t_ES_EVENT_TYPE_NOTIFY_GET_TASK_READ,
+    NotifyGetTaskInspect => es_event_type_t_ES_EVENT_TYPE_NOTIFY_GET_TASK_INSPECT,
+    NotifySetUid => es_event_type_t_ES_EVENT_TYPE_NOTIFY_SETUID,
+    NotifySetGid => es_event_type_t_ES_EVENT_TYPE_NOTIFY_SETGID,
+    NotifySetEUid => es_event_type_t_ES_EVENT_TYPE_NOTIFY_SETEUID,
+    NotifySetEGuid => es_event_type_t_ES_EVENT_TYPE_NOTIFY_SETEGID,
+    NotifySetREUid => es_event_type_t_ES_EVENT_TYPE_NOTIFY_SETREUID,
+    NotifySetREGuid => es_event_type_t_ES_EVENT_TYPE_NOTIFY_SETREGID,
+    AuthCopyFile => es_event_type_t_ES_EVENT_TYPE_AUTH_COPYFILE,
+    NotifyCopyFile => es_event_type_t_ES_EVENT_TYPE_NOTIFY_COPYFILE,
+    NotifyAuthentication => es_event_type_t_ES_EVENT_TYPE_NOTIFY_AUTHENTICATION,
+    NotifyXPMalwareDetected => es_event_type_t_ES_EVENT_TYPE_NOTIFY_XP_MALWARE_DETECTED,
+    NotifyXPMalwareRemediated => es_event_type_t_ES_EVENT_TYPE_NOTIFY_XP_MALWARE_REMEDIATED,
+    NotifyLWSessionLogin => es_event_type_t_ES_EVENT_TYPE_NOTIFY_LW_SESSION_LOGIN,
+    NotifyLWSessionLogout => es_event_type_t_ES_EVENT_TYPE_NOTIFY_LW_SESSION_LOGOUT,
+    NotifyLWSessionLock => es_event_type_t_ES_EVENT_TYPE_NOTIFY_LW_SESSION_LOCK,
+    NotifyLWSessionUnlock => es_event_type_t_ES_EVENT_TYPE_NOTIFY_LW_SESSION_UNLOCK,
+    NotifyScreenSharingAttach => es_event_type_t_ES_EVENT_TYPE_NOTIFY_SCREENSHARING_ATTACH,
+    NotifyScreenSharingDetach => es_event_type_t_ES_EVENT_TYPE_NOTIFY_SCREENSHARING_DETACH,
+    NotifyOpenSSHLogin => es_event_type_t_ES_EVENT_TYPE_NOTIFY_OPENSSH_LOGIN,
+    NotifyOpenSSHLogout => es_event_type_t_ES_EVENT_TYPE_NOTIFY_OPENSSH_LOGOUT,
+    NotifyLoginLogin => es_event_type_t_ES_EVENT_TYPE_NOTIFY_LOGIN_LOGIN,
+    NotifyLoginLogout => es_event_type_t_ES_EVENT_TYPE_NOTIFY_LOGIN_LOGOUT,
+    NotifyBTMLaunchItemAdd => es_event_type_t_ES_EVENT_TYPE_NOTIFY_BTM_LAUNCH_ITEM_ADD,
+    NotifyBTMLaunchItemRemove => es_event_type_t_ES_EVENT_TYPE_NOTIFY_BTM_LAUNCH_ITEM_REMOVE,
+}
+
+impl TryFrom<u32> for EsEventType {
+    type Error = EsError;
+
+    /// Unlike [EsEventType::from_raw], fails with [EsError::UnknownEvent] for unknown values
+    fn try_from(raw: u32) -> Result<Self, Self::Error> {
+        match EsEventType::from_raw(raw) {
+            EsEventType::Unknown(raw) => Err(EsError::UnknownEvent(raw)),
+            event => Ok(event),
+        }
+    }
+}
+
+impl EsEventType {
+    /// Whether auth messages of the event are answered with
+    /// [AuthRequest::respond_flags] instead of a plain allow/deny
+    pub fn accepts_flags_response(self) -> bool {
+        self == EsEventType::AuthOpen
+    }
+}
+
+impl From<EsEventType> for u32 {
+    fn from(event: EsEventType) -> Self {
+        event.raw()
+    }
 }
 
 /// The event type
-#[derive(Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum EsActionType {
     Auth,
     Notify,
+    /// Action added in a newer version of macOS
+    Unknown(u32),
 }
 
-#[repr(u32)]
+impl EsActionType {
+    fn from_raw(action: sys::es_action_type_t) -> Self {
+        match action {
+            sys::es_action_type_t_ES_ACTION_TYPE_AUTH => EsActionType::Auth,
+            sys::es_action_type_t_ES_ACTION_TYPE_NOTIFY => EsActionType::Notify,
+            action => EsActionType::Unknown(action),
+        }
+    }
+}
+
+/// How a muted path is matched, the `Target*` types match the files an event operates on
+/// instead of the executable of the process
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "config", derive(serde::Deserialize, serde::Serialize))]
 pub enum EsMutePath {
     Prefix,
     Literal,
+    TargetPrefix,
+    TargetLiteral,
+    /// Type added in a newer version of macOS
+    Unknown(u32),
+}
+
+impl EsMutePath {
+    fn from_raw(raw: sys::es_mute_path_type_t) -> Self {
+        match raw {
+            sys::es_mute_path_type_t_ES_MUTE_PATH_TYPE_PREFIX => EsMutePath::Prefix,
+            sys::es_mute_path_type_t_ES_MUTE_PATH_TYPE_LITERAL => EsMutePath::Literal,
+            sys::es_mute_path_type_t_ES_MUTE_PATH_TYPE_TARGET_PREFIX => EsMutePath::TargetPrefix,
+            sys::es_mute_path_type_t_ES_MUTE_PATH_TYPE_TARGET_LITERAL => EsMutePath::TargetLiteral,
+            raw => EsMutePath::Unknown(raw),
+        }
+    }
+
+    /// The raw `es_mute_path_type_t` value of the type
+    pub fn raw(self) -> u32 {
+        match self {
+            EsMutePath::Prefix => sys::es_mute_path_type_t_ES_MUTE_PATH_TYPE_PREFIX,
+            EsMutePath::Literal => sys::es_mute_path_type_t_ES_MUTE_PATH_TYPE_LITERAL,
+            EsMutePath::TargetPrefix => sys::es_mute_path_type_t_ES_MUTE_PATH_TYPE_TARGET_PREFIX,
+            EsMutePath::TargetLiteral => sys::es_mute_path_type_t_ES_MUTE_PATH_TYPE_TARGET_LITERAL,
+            EsMutePath::Unknown(raw) => raw,
+        }
+    }
+
+    /// Whether the type matches target paths rather than executables
+    pub fn is_target(self) -> bool {
+        matches!(self, EsMutePath::TargetPrefix | EsMutePath::TargetLiteral)
+    }
+}
+
+/// A path muted by the client, see [EsClient::muted_paths_events]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EsMutedPath {
+    pub path: PathBuf,
+    pub ty: EsMutePath,
+    /// events muted for the path
+    pub events: Vec<EsEventType>,
+}
+
+/// Kind of muting that can be inverted with [EsClient::invert_muting]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "config", derive(serde::Deserialize, serde::Serialize))]
+#[repr(u32)]
+pub enum EsMuteInversion {
+    Process,
+    Path,
+    TargetPath,
 }
 
 /// Info returned by Notify/Auth Rename events.
@@ -275,11 +529,7 @@ impl EsAddressType {
         if ty == 0 {
             EsAddressType::None
         } else {
-            let addr_str = unsafe {
-                std::ffi::CStr::from_ptr(str.data)
-                    .to_string_lossy()
-                    .to_string()
-            };
+            let addr_str = unsafe { es_string_to_string!(str.data) };
             match ty {
                 1 => EsAddressType::Ipv4(addr_str.parse().unwrap()),
                 2 => EsAddressType::Ipv6(addr_str.parse().unwrap()),
@@ -300,11 +550,7 @@ pub struct EsSshLogin {
 
 impl From<&sys::es_event_openssh_login_t> for EsSshLogin {
     fn from(value: &sys::es_event_openssh_login_t) -> Self {
-        let username = unsafe {
-            std::ffi::CStr::from_ptr(value.username.data)
-                .to_string_lossy()
-                .to_string()
-        };
+        let username = unsafe { es_string_to_string!(value.username.data) };
 
         let result = match value.result_type {
             0 => EsSSHLoginResult::LoginExceedMaxTries,
@@ -344,11 +590,7 @@ pub struct EsSSHLogout {
 impl From<&sys::es_event_openssh_logout_t> for EsSSHLogout {
     fn from(value: &sys::es_event_openssh_logout_t) -> Self {
         Self {
-            username: unsafe {
-                std::ffi::CStr::from_ptr(value.username.data)
-                    .to_string_lossy()
-                    .to_string()
-            },
+            username: unsafe { es_string_to_string!(value.username.data) },
             uid: value.uid,
             source_address: EsAddressType::parse(&value.source_address, value.source_address_type),
         }
@@ -361,13 +603,13 @@ pub struct EsUnlinkFile {
     pub target: EsFile,
 }
 
-impl From<sys::es_event_unlink_t> for EsUnlinkFile {
-    fn from(value: sys::es_event_unlink_t) -> Self {
+impl EsUnlinkFile {
+    fn new(value: &sys::es_event_unlink_t) -> Option<Self> {
         unsafe {
-            Self {
-                parent_dir: value.parent_dir.as_ref().unwrap().into(),
-                target: value.target.as_ref().unwrap().into(),
-            }
+            Some(Self {
+                parent_dir: value.parent_dir.as_ref()?.into(),
+                target: value.target.as_ref()?.into(),
+            })
         }
     }
 }
@@ -389,9 +631,7 @@ impl From<sys::es_event_copyfile_t> for EsCopyFile {
                 source: value.source.as_ref().map(|src| src.into()),
                 target_file: value.target_file.as_ref().map(|tar| tar.into()),
                 target_dir: value.target_dir.as_ref().map(|t_dir| t_dir.into()),
-                target_name: CStr::from_ptr(value.target_name.data)
-                    .to_string_lossy()
-                    .to_string(),
+                target_name: es_string_to_string!(value.target_name.data),
                 mode: value.mode,
                 flags: value.flags,
             }
@@ -450,13 +690,13 @@ pub struct EsClone {
     pub name: String,
 }
 
-impl From<sys::es_event_clone_t> for EsClone {
-    fn from(value: sys::es_event_clone_t) -> Self {
-        Self {
-            source: unsafe { value.source.as_ref().unwrap().into() },
-            target_dir: unsafe { value.target_dir.as_ref().unwrap().into() },
+impl EsClone {
+    fn new(value: &sys::es_event_clone_t) -> Option<Self> {
+        Some(Self {
+            source: unsafe { value.source.as_ref() }?.into(),
+            target_dir: unsafe { value.target_dir.as_ref() }?.into(),
             name: unsafe { es_string_to_string!(value.target_name.data) },
-        }
+        })
     }
 }
 
@@ -469,18 +709,109 @@ pub struct EsMMap {
     pub source: EsFile,
 }
 
-impl From<sys::es_event_mmap_t> for EsMMap {
-    fn from(value: sys::es_event_mmap_t) -> Self {
-        Self {
+impl EsMMap {
+    fn new(value: &sys::es_event_mmap_t) -> Option<Self> {
+        Some(Self {
             protection: value.protection,
             max_protection: value.max_protection,
             flags: value.flags,
             file_pos: value.file_pos,
-            source: unsafe { value.source.as_ref() }.unwrap().into(),
+            source: unsafe { value.source.as_ref() }?.into(),
+        })
+    }
+}
+
+/// `fdtype` of a pipe, see `<sys/proc_info.h>`
+const PROX_FDTYPE_PIPE: u32 = 6;
+
+/// File descriptor inherited by an executed process
+#[derive(Debug)]
+pub struct EsFd {
+    pub fd: i32,
+    /// `PROX_FDTYPE_*` value from `<sys/proc_info.h>`
+    pub fdtype: u32,
+    /// Unique id of the pipe if the descriptor is one
+    pub pipe_id: Option<u64>,
+}
+
+impl From<&sys::es_fd_t> for EsFd {
+    fn from(value: &sys::es_fd_t) -> Self {
+        Self {
+            fd: value.fd,
+            fdtype: value.fdtype,
+            pipe_id: if value.fdtype == PROX_FDTYPE_PIPE {
+                Some(unsafe { value.__bindgen_anon_1.pipe.pipe_id })
+            } else {
+                None
+            },
         }
     }
 }
 
+/// Info returned by Notify/Auth Exec events.
+///
+/// Fields added in later versions of Endpoint Security are empty on systems that don't send them.
+#[derive(Debug)]
+pub struct EsExec {
+    /// The new process image
+    pub target: EsProcess,
+    /// command line arguments, including argv\[0\]
+    pub args: Vec<String>,
+    /// environment variables as `KEY=value`
+    pub env: Vec<String>,
+    /// file descriptors the new image inherits
+    pub fds: Vec<EsFd>,
+    /// script being executed by an interpreter, if any
+    pub script: Option<EsFile>,
+    /// working directory of the new image
+    pub cwd: Option<EsFile>,
+    /// highest open file descriptor after the exec
+    pub last_fd: Option<i32>,
+    /// cpu type of the executable image
+    pub image_cputype: Option<i32>,
+    /// cpu subtype of the executable image
+    pub image_cpusubtype: Option<i32>,
+}
+
+impl EsExec {
+    fn new(exec: &sys::es_event_exec_t, version: u32, backend: &dyn EsBackend) -> Option<Self> {
+        let target = EsProcess::new(unsafe { exec.target.as_ref() }?, version);
+        let fields = unsafe { exec.__bindgen_anon_1.__bindgen_anon_1 };
+
+        Some(Self {
+            target,
+            args: backend
+                .exec_args(exec)
+                .iter()
+                .map(|arg| unsafe { es_string_to_string!(arg.data) })
+                .collect(),
+            env: backend
+                .exec_envs(exec)
+                .iter()
+                .map(|env| unsafe { es_string_to_string!(env.data) })
+                .collect(),
+            fds: if version >= 4 {
+                backend.exec_fds(exec).into_iter().map(EsFd::from).collect()
+            } else {
+                vec![]
+            },
+            script: if version >= 2 {
+                unsafe { fields.script.as_ref() }.map(EsFile::from)
+            } else {
+                None
+            },
+            cwd: if version >= 3 {
+                unsafe { fields.cwd.as_ref() }.map(EsFile::from)
+            } else {
+                None
+            },
+            last_fd: (version >= 4).then_some(fields.last_fd),
+            image_cputype: (version >= 6).then_some(fields.image_cputype),
+            image_cpusubtype: (version >= 6).then_some(fields.image_cpusubtype),
+        })
+    }
+}
+
 #[derive(Debug)]
 pub struct EsScreenSharingAttach {
     pub success: bool,
@@ -508,239 +839,1081 @@ impl From<sys::es_event_screensharing_attach_t> for EsScreenSharingAttach {
     }
 }
 
-#[derive(Debug)]
-pub enum EsEventData {
-    AuthOpen(EsFile),
-    AuthRename(EsRename),
-    AuthUnlink(EsUnlinkFile),
-    AuthReadDir(EsFile),
-    AuthChroot(EsFile),
-    AuthCopyFile(EsCopyFile),
-    NotifyCopyFile(EsCopyFile),
-
-    NotifyClone(EsClone),
-    AuthClone(EsClone),
-
-    NotifyMMap(EsMMap),
-    AuthMMap(EsMMap),
-
-    NotifyExit(i32),
-
-    NotifyOpen(EsFile),
-    NotifyExec(EsProcess),
-    NotifyWrite(EsFile),
-    NotifyRename(EsRename),
-    NotifyReadDir(EsFile),
-    NotifyChroot(EsFile),
-    /// 2nd argument is true if the file was modified
-    NotifyClose((EsFile, bool)),
-    NotifyOpenSSHLogin(EsSshLogin),
-    NotifyOpenSSHLogout(EsSSHLogout),
-    NotifyLWSessionLock(EsLWSession),
-    NotifyLWSessionUnlock(EsLWSession),
-    NotifyLWSessionLogin(EsLWSession),
-    NotifyLWSessionLogout(EsLWSession),
-}
-
-impl From<sys::es_event_rename_t> for EsRename {
-    fn from(value: sys::es_event_rename_t) -> Self {
-        let source = unsafe { value.source.as_ref().unwrap().into() };
-
-        let mut rename_info = Self {
-            source,
-            destination_existing: None,
-            destintaion_newpath: None,
-        };
-
-        if value.destination_type == 0 {
-            rename_info.destination_existing = unsafe {
-                value
-                    .destination
-                    .existing_file
-                    .as_ref()
-                    .map(|file| file.into())
-            };
-        } else {
-            rename_info.destintaion_newpath = Some((
-                unsafe { value.destination.new_path.dir.as_ref().unwrap().into() },
-                unsafe {
-                    std::ffi::CStr::from_ptr(value.destination.new_path.filename.data)
-                        .to_string_lossy()
-                        .to_string()
-                },
-            ));
-        }
-
-        rename_info
+bitflags::bitflags! {
+    /// Kernel `fflag` of an open, the `F*`/`O_*` flags from `<sys/fcntl.h>`
+    #[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+    pub struct EsOpenFlags: u32 {
+        const READ = 0x0000_0001;
+        const WRITE = 0x0000_0002;
+        const NONBLOCK = 0x0000_0004;
+        const APPEND = 0x0000_0008;
+        const SHLOCK = 0x0000_0010;
+        const EXLOCK = 0x0000_0020;
+        const ASYNC = 0x0000_0040;
+        const FSYNC = 0x0000_0080;
+        const NOFOLLOW = 0x0000_0100;
+        const CREAT = 0x0000_0200;
+        const TRUNC = 0x0000_0400;
+        const EXCL = 0x0000_0800;
+        const EVTONLY = 0x0000_8000;
+        const NOCTTY = 0x0002_0000;
+        const DIRECTORY = 0x0010_0000;
+        const SYMLINK = 0x0020_0000;
+        const CLOEXEC = 0x0100_0000;
     }
 }
 
+/// Info returned by Notify/Auth Open events.
 #[derive(Debug)]
-pub struct EsFile {
-    pub path: String,
-    pub path_truncated: bool,
+pub struct EsOpen {
+    pub file: EsFile,
+    /// flags the file is opened with, for auth events the ones that are requested
+    pub fflag: EsOpenFlags,
 }
 
-impl From<&sys::es_file_t> for EsFile {
-    fn from(file: &sys::es_file_t) -> Self {
-        let path = unsafe { std::ffi::CStr::from_ptr(file.path.data) }
-            .to_string_lossy()
-            .to_string();
-
-        Self {
-            path,
-            path_truncated: file.path_truncated,
-        }
+impl EsOpen {
+    fn new(open: &sys::es_event_open_t) -> Option<Self> {
+        Some(Self {
+            file: unsafe { open.file.as_ref() }?.into(),
+            fflag: EsOpenFlags::from_bits_retain(open.fflag as u32),
+        })
     }
 }
 
+/// Info returned by Notify/Auth Set Mode events
 #[derive(Debug)]
-pub struct EsProcess {
-    /// process pid
-    pub pid: i32,
-    /// Parent pid
-    pub ppid: i32,
-    /// groupd id
-    pub gid: i32,
-    pub exe: EsFile,
-    audit_token: sys::audit_token_t,
+pub struct EsSetMode {
+    pub target: EsFile,
+    /// the new mode, see `chmod(2)`
+    pub mode: u16,
 }
 
-impl EsProcess {
-    pub fn audit_token(&self) -> sys::audit_token_t {
-        self.audit_token
+impl EsSetMode {
+    fn new(event: &sys::es_event_setmode_t) -> Option<Self> {
+        Some(Self {
+            target: unsafe { event.target.as_ref() }?.into(),
+            mode: event.mode,
+        })
     }
+}
 
-    pub fn mute(&self, client: &EsClient) {
-        unsafe { sys::es_mute_process(client.client as _, &self.audit_token as _) };
+bitflags::bitflags! {
+    /// File flags set with `chflags(2)`, the `UF_*` and `SF_*` flags from `<sys/stat.h>`
+    #[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+    pub struct EsFileFlags: u32 {
+        const UF_NODUMP = 0x0000_0001;
+        const UF_IMMUTABLE = 0x0000_0002;
+        const UF_APPEND = 0x0000_0004;
+        const UF_OPAQUE = 0x0000_0008;
+        const UF_COMPRESSED = 0x0000_0020;
+        const UF_TRACKED = 0x0000_0040;
+        const UF_DATAVAULT = 0x0000_0080;
+        const UF_HIDDEN = 0x0000_8000;
+        const SF_ARCHIVED = 0x0001_0000;
+        const SF_IMMUTABLE = 0x0002_0000;
+        const SF_APPEND = 0x0004_0000;
+        const SF_RESTRICTED = 0x0008_0000;
+        const SF_NOUNLINK = 0x0010_0000;
+        const SF_FIRMLINK = 0x0080_0000;
+        const SF_DATALESS = 0x4000_0000;
     }
 }
 
-impl From<&sys::es_process_t> for EsProcess {
-    fn from(value: &sys::es_process_t) -> Self {
-        let pid = unsafe { bsm::audit_token_to_pid(value.audit_token) };
+/// Info returned by Notify/Auth Set Flags events
+#[derive(Debug)]
+pub struct EsSetFlags {
+    pub target: EsFile,
+    /// the new flags
+    pub flags: EsFileFlags,
+}
 
-        Self {
-            audit_token: value.audit_token,
-            ppid: value.ppid,
-            gid: value.group_id,
-            exe: unsafe { value.executable.as_ref().unwrap().into() },
-            pid,
-        }
+impl EsSetFlags {
+    fn new(event: &sys::es_event_setflags_t) -> Option<Self> {
+        Some(Self {
+            target: unsafe { event.target.as_ref() }?.into(),
+            flags: EsFileFlags::from_bits_retain(event.flags),
+        })
     }
 }
 
+/// Info returned by Notify/Auth Set Owner events
 #[derive(Debug)]
-pub struct EsLWSession {
-    pub graphical_session_id: u32,
-    pub username: String,
+pub struct EsSetOwner {
+    pub target: EsFile,
+    /// the new owner
+    pub uid: u32,
+    /// the new group
+    pub gid: u32,
 }
 
-impl EsLWSession {
-    fn from_es_type(id: u32, str: *const i8) -> Self {
-        Self {
-            graphical_session_id: id,
-            username: unsafe { CStr::from_ptr(str).to_string_lossy().to_string() },
-        }
+impl EsSetOwner {
+    fn new(event: &sys::es_event_setowner_t) -> Option<Self> {
+        Some(Self {
+            target: unsafe { event.target.as_ref() }?.into(),
+            uid: event.uid,
+            gid: event.gid,
+        })
     }
 }
 
-pub struct EsUser {
-    pub uid: u32,
-    pub username: String,
+/// Change made by a Set ACL event
+#[derive(Debug, Eq, PartialEq)]
+pub enum EsAcl {
+    /// An ACL is set on the file
+    Set {
+        /// number of access control entries
+        entries: usize,
+        /// the ACL as formatted by `acl_to_text`
+        text: Option<String>,
+    },
+    /// The ACL of the file is removed
+    Clear,
 }
 
-pub struct EsLogin {
-    pub success: bool,
-    pub err: Option<String>,
-    pub user: EsUser,
+/// Info returned by Notify/Auth Set ACL events
+#[derive(Debug)]
+pub struct EsSetAcl {
+    pub target: EsFile,
+    pub acl: EsAcl,
 }
 
-impl From<&sys::es_event_login_login_t> for EsLogin {
-    fn from(value: &sys::es_event_login_login_t) -> Self {
-        Self {
-            success: value.success,
-            err: if !value.success {
-                Some(unsafe { es_string_to_string!(value.failure_message.data) })
-            } else {
-                None
-            },
-            user: EsUser {
-                uid: unsafe { value.uid.uid },
-                username: unsafe { es_string_to_string!(value.username.data) },
-            },
-        }
+impl EsSetAcl {
+    fn new(event: &sys::es_event_setacl_t, backend: &dyn EsBackend) -> Option<Self> {
+        let acl = if event.set_or_clear == sys::es_set_or_clear_t_ES_CLEAR {
+            EsAcl::Clear
+        } else {
+            EsAcl::Set {
+                entries: backend.acl_entry_count(event),
+                text: backend.acl_text(event),
+            }
+        };
+
+        Some(Self {
+            target: unsafe { event.target.as_ref() }?.into(),
+            acl,
+        })
     }
 }
 
+/// Info returned by Notify/Auth Utimes events
 #[derive(Debug)]
-pub struct EsMessage {
-    pub action: EsActionType,
-    pub event: EsEventType,
-    pub event_data: Option<EsEventData>,
-    pub version: u32,
-    pub seq_num: u64,
-    pub process: Option<EsProcess>,
-    pub thread_id: Option<u64>,
-    message_ptr: *const sys::es_message_t,
+pub struct EsUtimes {
+    pub target: EsFile,
+    /// the new access time, `None` if it is out of range, like the `UTIME_NOW` and
+    /// `UTIME_OMIT` values of `utimensat(2)`
+    pub atime: Option<std::time::SystemTime>,
+    /// the new modification time, `None` if it is out of range
+    pub mtime: Option<std::time::SystemTime>,
 }
 
-impl EsMessage {
-    pub fn allow(&self, client: &EsClient) {
-        if self.action == EsActionType::Auth {
-            assert!(
-                unsafe { sys::es_respond_auth_result(client.client, self.message_ptr, 0, true) }
-                    == 0
-            );
-        }
+impl EsUtimes {
+    fn new(event: &sys::es_event_utimes_t) -> Option<Self> {
+        Some(Self {
+            target: unsafe { event.target.as_ref() }?.into(),
+            atime: epoch_time(event.atime.tv_sec, event.atime.tv_nsec),
+            mtime: epoch_time(event.mtime.tv_sec, event.mtime.tv_nsec),
+        })
     }
+}
+
+/// Extended attributes set by Apple software
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum EsAppleExtAttr {
+    /// `com.apple.quarantine`, marks downloaded files for Gatekeeper. Removing it skips the
+    /// Gatekeeper check on first launch.
+    Quarantine,
+    /// `com.apple.provenance`, tracks the app that created or modified a file
+    Provenance,
+    /// `com.apple.metadata:kMDItemWhereFroms`, the URLs a file was downloaded from
+    WhereFroms,
+    /// `com.apple.FinderInfo`
+    FinderInfo,
+    /// `com.apple.ResourceFork`
+    ResourceFork,
+    /// `com.apple.decmpfs`, data of transparently compressed files
+    Decmpfs,
+    /// `com.apple.rootless`, exempts a file from System Integrity Protection
+    Rootless,
+    /// `com.apple.macl`, files an app was granted access to through TCC
+    Macl,
+    /// `com.apple.lastuseddate#PS`
+    LastUsedDate,
+}
 
-    pub fn deny(&self, client: &EsClient) {
-        if self.action == EsActionType::Auth {
-            assert!(
-                unsafe { sys::es_respond_auth_result(client.client, self.message_ptr, 1, true) }
-                    == 0
-            );
+impl EsAppleExtAttr {
+    const ALL: [Self; 9] = [
+        Self::Quarantine,
+        Self::Provenance,
+        Self::WhereFroms,
+        Self::FinderInfo,
+        Self::ResourceFork,
+        Self::Decmpfs,
+        Self::Rootless,
+        Self::Macl,
+        Self::LastUsedDate,
+    ];
+
+    /// Name of the attribute
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Quarantine => "com.apple.quarantine",
+            Self::Provenance => "com.apple.provenance",
+            Self::WhereFroms => "com.apple.metadata:kMDItemWhereFroms",
+            Self::FinderInfo => "com.apple.FinderInfo",
+            Self::ResourceFork => "com.apple.ResourceFork",
+            Self::Decmpfs => "com.apple.decmpfs",
+            Self::Rootless => "com.apple.rootless",
+            Self::Macl => "com.apple.macl",
+            Self::LastUsedDate => "com.apple.lastuseddate#PS",
         }
     }
-}
 
-impl Drop for EsMessage {
-    fn drop(&mut self) {
-        unsafe { sys::es_release_message(self.message_ptr) };
+    /// The attribute called `name`, `None` if it isn't a well-known one
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|attr| attr.name() == name)
     }
 }
 
-impl From<&sys::es_message_t> for EsMessage {
-    fn from(message: &sys::es_message_t) -> Self {
-        let action = match message.action_type {
-            0 => EsActionType::Auth,
-            1 => EsActionType::Notify,
-            _ => panic!("Useless"),
+/// Info returned by Notify/Auth Set, Get and Delete Extended Attribute events
+#[derive(Debug)]
+pub struct EsExtAttr {
+    pub target: EsFile,
+    /// name of the attribute
+    pub name: String,
+}
+
+impl EsExtAttr {
+    fn new(target: *const sys::es_file_t, name: &sys::es_string_token_t) -> Option<Self> {
+        Some(Self {
+            target: unsafe { target.as_ref() }?.into(),
+            name: unsafe { es_string_to_opt_string!(name.data) }.unwrap_or_default(),
+        })
+    }
+
+    /// The attribute if it's a well-known Apple one, see [EsAppleExtAttr::from_name]
+    pub fn apple_attr(&self) -> Option<EsAppleExtAttr> {
+        EsAppleExtAttr::from_name(&self.name)
+    }
+}
+
+fn c_array_to_string(array: &[std::ffi::c_char]) -> String {
+    let bytes: Vec<u8> = array
+        .iter()
+        .take_while(|&&c| c != 0)
+        .map(|&c| c as u8)
+        .collect();
+    String::from_utf8_lossy(&bytes).to_string()
+}
+
+bitflags::bitflags! {
+    /// Flags of a mounted file system, the `MNT_*` flags from `<sys/mount.h>`
+    #[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+    pub struct EsMountFlags: u32 {
+        const RDONLY = 0x0000_0001;
+        const SYNCHRONOUS = 0x0000_0002;
+        const NOEXEC = 0x0000_0004;
+        const NOSUID = 0x0000_0008;
+        const NODEV = 0x0000_0010;
+        const UNION = 0x0000_0020;
+        const ASYNC = 0x0000_0040;
+        const CPROTECT = 0x0000_0080;
+        const EXPORTED = 0x0000_0100;
+        const REMOVABLE = 0x0000_0200;
+        const QUARANTINE = 0x0000_0400;
+        const LOCAL = 0x0000_1000;
+        const QUOTA = 0x0000_2000;
+        const ROOTFS = 0x0000_4000;
+        const DOVOLFS = 0x0000_8000;
+        const DONTBROWSE = 0x0010_0000;
+        const IGNORE_OWNERSHIP = 0x0020_0000;
+        const AUTOMOUNTED = 0x0040_0000;
+        const JOURNALED = 0x0080_0000;
+        const NOUSERXATTR = 0x0100_0000;
+        const DEFWRITE = 0x0200_0000;
+        const MULTILABEL = 0x0400_0000;
+        const NOFOLLOW = 0x0800_0000;
+        const NOATIME = 0x1000_0000;
+        const SNAPSHOT = 0x4000_0000;
+        const STRICTATIME = 0x8000_0000;
+    }
+}
+
+/// Info returned by Notify/Auth Mount, Notify Unmount and Notify/Auth Remount events, read from
+/// the `statfs` of the file system
+#[derive(Debug)]
+pub struct EsMount {
+    /// file system type, like `apfs`, `msdos` or `exfat`
+    pub fs_type: String,
+    /// file system subtype, see `statfs(2)`
+    pub fs_subtype: u32,
+    /// directory the file system is mounted on
+    pub mount_on: String,
+    /// mounted file system, usually the device like `/dev/disk4s1`
+    pub mount_from: String,
+    pub flags: EsMountFlags,
+    /// user that mounted the file system
+    pub owner: u32,
+}
+
+impl EsMount {
+    fn new(statfs: *const sys::statfs) -> Option<Self> {
+        let statfs = unsafe { statfs.as_ref() }?;
+        Some(Self {
+            fs_type: c_array_to_string(&statfs.f_fstypename),
+            fs_subtype: statfs.f_fssubtype,
+            mount_on: c_array_to_string(&statfs.f_mntonname),
+            mount_from: c_array_to_string(&statfs.f_mntfromname),
+            flags: EsMountFlags::from_bits_retain(statfs.f_flags),
+            owner: statfs.f_owner,
+        })
+    }
+}
+
+/// Info returned by Notify Fork events
+#[derive(Debug)]
+pub struct EsFork {
+    /// The new process
+    pub child: EsProcess,
+}
+
+impl EsFork {
+    fn new(fork: &sys::es_event_fork_t, version: u32) -> Option<Self> {
+        Some(Self {
+            child: EsProcess::new(unsafe { fork.child.as_ref() }?, version),
+        })
+    }
+}
+
+/// Info returned by Notify/Auth Signal events
+#[derive(Debug)]
+pub struct EsSignal {
+    /// signal number, e.g. `libc::SIGKILL`
+    pub signal: i32,
+    /// The process the signal is sent to
+    pub target: EsProcess,
+}
+
+impl EsSignal {
+    fn new(signal: &sys::es_event_signal_t, version: u32) -> Option<Self> {
+        Some(Self {
+            signal: signal.sig,
+            target: EsProcess::new(unsafe { signal.target.as_ref() }?, version),
+        })
+    }
+}
+
+/// Kind of process information requested by a Proc Check event
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum EsProcCheckType {
+    ListPids,
+    PidInfo,
+    PidFdInfo,
+    KernMsgBuf,
+    SetControl,
+    PidFilePortInfo,
+    Terminate,
+    DirtyControl,
+    PidRUsage,
+    UDataInfo,
+    /// a type added after this crate was built
+    Unknown(u32),
+}
+
+impl EsProcCheckType {
+    fn from_raw(ty: sys::es_proc_check_type_t) -> Self {
+        match ty {
+            sys::es_proc_check_type_t_ES_PROC_CHECK_TYPE_LISTPIDS => Self::ListPids,
+            sys::es_proc_check_type_t_ES_PROC_CHECK_TYPE_PIDINFO => Self::PidInfo,
+            sys::es_proc_check_type_t_ES_PROC_CHECK_TYPE_PIDFDINFO => Self::PidFdInfo,
+            sys::es_proc_check_type_t_ES_PROC_CHECK_TYPE_KERNMSGBUF => Self::KernMsgBuf,
+            sys::es_proc_check_type_t_ES_PROC_CHECK_TYPE_SETCONTROL => Self::SetControl,
+            sys::es_proc_check_type_t_ES_PROC_CHECK_TYPE_PIDFILEPORTINFO => Self::PidFilePortInfo,
+            sys::es_proc_check_type_t_ES_PROC_CHECK_TYPE_TERMINATE => Self::Terminate,
+            sys::es_proc_check_type_t_ES_PROC_CHECK_TYPE_DIRTYCONTROL => Self::DirtyControl,
+            sys::es_proc_check_type_t_ES_PROC_CHECK_TYPE_PIDRUSAGE => Self::PidRUsage,
+            sys::es_proc_check_type_t_ES_PROC_CHECK_TYPE_UDATA_INFO => Self::UDataInfo,
+            ty => Self::Unknown(ty),
+        }
+    }
+}
+
+/// Info returned by Notify/Auth Proc Check events, sent when a process asks for information
+/// about another one, e.g. through `proc_pidinfo`
+#[derive(Debug)]
+pub struct EsProcCheck {
+    /// The process being inspected, `None` for checks that don't target a process
+    pub target: Option<EsProcess>,
+    pub ty: EsProcCheckType,
+    /// `flavor` argument of the check, its meaning depends on `ty`
+    pub flavor: i32,
+}
+
+impl EsProcCheck {
+    fn new(check: &sys::es_event_proc_check_t, version: u32) -> Self {
+        Self {
+            target: unsafe { check.target.as_ref() }.map(|target| EsProcess::new(target, version)),
+            ty: EsProcCheckType::from_raw(check.type_),
+            flavor: check.flavor,
+        }
+    }
+}
+
+/// What a Proc Suspend Resume event does to its target
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum EsProcSuspendResumeType {
+    Suspend,
+    Resume,
+    /// the sockets of the target are shut down, see `pid_shutdown_sockets`
+    ShutdownSockets,
+    /// a type added after this crate was built
+    Unknown(u32),
+}
+
+/// Info returned by Notify/Auth Proc Suspend Resume events
+#[derive(Debug)]
+pub struct EsProcSuspendResume {
+    /// The process being suspended or resumed, if ES reports it
+    pub target: Option<EsProcess>,
+    pub ty: EsProcSuspendResumeType,
+}
+
+impl EsProcSuspendResume {
+    fn new(event: &sys::es_event_proc_suspend_resume_t, version: u32) -> Self {
+        let ty = match event.type_ {
+            sys::es_proc_suspend_resume_type_t_ES_PROC_SUSPEND_RESUME_TYPE_SUSPEND => {
+                EsProcSuspendResumeType::Suspend
+            }
+            sys::es_proc_suspend_resume_type_t_ES_PROC_SUSPEND_RESUME_TYPE_RESUME => {
+                EsProcSuspendResumeType::Resume
+            }
+            sys::es_proc_suspend_resume_type_t_ES_PROC_SUSPEND_RESUME_TYPE_SHUTDOWN_SOCKETS => {
+                EsProcSuspendResumeType::ShutdownSockets
+            }
+            ty => EsProcSuspendResumeType::Unknown(ty),
+        };
+
+        Self {
+            target: unsafe { event.target.as_ref() }.map(|target| EsProcess::new(target, version)),
+            ty,
+        }
+    }
+}
+
+/// How a task port is being obtained
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum EsGetTaskType {
+    /// through `task_for_pid`
+    TaskForPid,
+    /// through `processor_set_tasks` or similar calls exposing task ports
+    ExposeTask,
+    /// through `task_identity_token_get_task_port`
+    IdentityToken,
+    /// a type added after this crate was built
+    Unknown(u32),
+}
+
+/// Info returned by the Get Task events: Get Task, Get Task Read, Get Task Inspect and Get Task
+/// Name, which differ in the rights granted on the task port
+#[derive(Debug)]
+pub struct EsGetTask {
+    /// The process whose task port is requested
+    pub target: EsProcess,
+    /// How the port is obtained, only sent in message version 5+
+    pub ty: Option<EsGetTaskType>,
+}
+
+impl EsGetTask {
+    fn new(
+        target: *const sys::es_process_t,
+        ty: sys::es_get_task_type_t,
+        version: u32,
+    ) -> Option<Self> {
+        let ty = match ty {
+            sys::es_get_task_type_t_ES_GET_TASK_TYPE_TASK_FOR_PID => EsGetTaskType::TaskForPid,
+            sys::es_get_task_type_t_ES_GET_TASK_TYPE_EXPOSE_TASK => EsGetTaskType::ExposeTask,
+            sys::es_get_task_type_t_ES_GET_TASK_TYPE_IDENTITY_TOKEN => EsGetTaskType::IdentityToken,
+            ty => EsGetTaskType::Unknown(ty),
+        };
+
+        Some(Self {
+            target: EsProcess::new(unsafe { target.as_ref() }?, version),
+            ty: (version >= 5).then_some(ty),
+        })
+    }
+}
+
+/// Info returned by Notify Trace events, sent when a process attaches to another one with
+/// `ptrace`
+#[derive(Debug)]
+pub struct EsTrace {
+    /// The process being attached to
+    pub target: EsProcess,
+}
+
+impl EsTrace {
+    fn new(trace: &sys::es_event_trace_t, version: u32) -> Option<Self> {
+        Some(Self {
+            target: EsProcess::new(unsafe { trace.target.as_ref() }?, version),
+        })
+    }
+}
+
+/// Register state a remote thread starts with
+#[derive(Debug)]
+pub struct EsThreadState {
+    /// thread state flavor, e.g. `ARM_THREAD_STATE64`
+    pub flavor: i32,
+    /// the raw thread state
+    pub state: Vec<u8>,
+}
+
+impl From<&sys::es_thread_state_t> for EsThreadState {
+    fn from(value: &sys::es_thread_state_t) -> Self {
+        let state = if value.state.data.is_null() {
+            vec![]
+        } else {
+            unsafe { std::slice::from_raw_parts(value.state.data, value.state.size) }.to_vec()
+        };
+
+        Self {
+            flavor: value.flavor,
+            state,
+        }
+    }
+}
+
+/// Info returned by Notify Remote Thread Create events, sent when a thread is created in
+/// another process
+#[derive(Debug)]
+pub struct EsRemoteThreadCreate {
+    /// The process the thread is created in
+    pub target: EsProcess,
+    /// State of the new thread, `None` if it is created without one (`thread_create` instead of
+    /// `thread_create_running`)
+    pub thread_state: Option<EsThreadState>,
+}
+
+impl EsRemoteThreadCreate {
+    fn new(event: &sys::es_event_remote_thread_create_t, version: u32) -> Option<Self> {
+        Some(Self {
+            target: EsProcess::new(unsafe { event.target.as_ref() }?, version),
+            thread_state: unsafe { event.thread_state.as_ref() }.map(EsThreadState::from),
+        })
+    }
+}
+
+/// How a process ended, decoded from a `wait(2)` status
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum EsExitStatus {
+    /// The process exited with this code
+    Exited(i32),
+    /// The process was terminated by a signal
+    Signaled { signal: i32, core_dumped: bool },
+    /// The process was stopped by a signal
+    Stopped(i32),
+    /// The process was continued
+    Continued,
+}
+
+impl EsExitStatus {
+    /// Decode `stat` like the `W*` macros of `<sys/wait.h>`
+    pub fn from_raw(stat: i32) -> Self {
+        const WSTOPPED: i32 = 0o177;
+        const SIGCONT: i32 = 0x13;
+
+        let status = stat & 0o177;
+        let high = (stat >> 8) & 0xff;
+        match status {
+            0 => Self::Exited(high),
+            WSTOPPED if high == SIGCONT => Self::Continued,
+            WSTOPPED => Self::Stopped(high),
+            signal => Self::Signaled {
+                signal,
+                core_dumped: stat & 0o200 != 0,
+            },
+        }
+    }
+}
+
+/// Info returned by Notify Exit events
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct EsExit {
+    /// raw `wait(2)` status
+    pub stat: i32,
+    pub status: EsExitStatus,
+}
+
+impl From<&sys::es_event_exit_t> for EsExit {
+    fn from(exit: &sys::es_event_exit_t) -> Self {
+        Self {
+            stat: exit.stat,
+            status: EsExitStatus::from_raw(exit.stat),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum EsEventData {
+    AuthOpen(EsOpen),
+    AuthRename(EsRename),
+    AuthUnlink(EsUnlinkFile),
+    AuthReadDir(EsFile),
+    AuthChroot(EsFile),
+    AuthCopyFile(EsCopyFile),
+    NotifyCopyFile(EsCopyFile),
+
+    NotifyClone(EsClone),
+    AuthClone(EsClone),
+
+    NotifyMMap(EsMMap),
+    AuthMMap(EsMMap),
+
+    NotifyExit(EsExit),
+    AuthSetMode(EsSetMode),
+    NotifySetMode(EsSetMode),
+    AuthSetFlags(EsSetFlags),
+    NotifySetFlags(EsSetFlags),
+    AuthSetOwner(EsSetOwner),
+    NotifySetOwner(EsSetOwner),
+    AuthSetACL(EsSetAcl),
+    NotifySetACL(EsSetAcl),
+    AuthUtimes(EsUtimes),
+    NotifyUtimes(EsUtimes),
+    AuthTruncate(EsFile),
+    NotifyTruncate(EsFile),
+    AuthSetExtAttr(EsExtAttr),
+    NotifySetExtAttr(EsExtAttr),
+    AuthGetExtAttr(EsExtAttr),
+    NotifyGetExtAttr(EsExtAttr),
+    AuthDeleteExtAttr(EsExtAttr),
+    NotifyDeleteExtAttr(EsExtAttr),
+    AuthListenExtAttr(EsFile),
+    NotifyListenExtAttr(EsFile),
+    AuthMount(EsMount),
+    NotifyMount(EsMount),
+    NotifyUnmount(EsMount),
+    AuthRemount(EsMount),
+    NotifyRemount(EsMount),
+    NotifyFork(EsFork),
+    AuthSignal(EsSignal),
+    NotifySignal(EsSignal),
+    AuthProcCheck(EsProcCheck),
+    NotifyProcCheck(EsProcCheck),
+    AuthProcSuspendResume(EsProcSuspendResume),
+    NotifyProcSuspendResume(EsProcSuspendResume),
+    AuthGetTask(EsGetTask),
+    NotifyGetTask(EsGetTask),
+    AuthGetTaskRead(EsGetTask),
+    NotifyGetTaskRead(EsGetTask),
+    NotifyGetTaskInspect(EsGetTask),
+    NotifyGetTaskName(EsGetTask),
+    NotfiyTrace(EsTrace),
+    NotifyRemoteThreadCreate(EsRemoteThreadCreate),
+
+    NotifyOpen(EsOpen),
+    AuthExec(EsExec),
+    NotifyExec(EsExec),
+    NotifyWrite(EsFile),
+    NotifyRename(EsRename),
+    NotifyReadDir(EsFile),
+    NotifyChroot(EsFile),
+    /// 2nd argument is true if the file was modified
+    NotifyClose((EsFile, bool)),
+    NotifyOpenSSHLogin(EsSshLogin),
+    NotifyOpenSSHLogout(EsSSHLogout),
+    NotifyLWSessionLock(EsLWSession),
+    NotifyLWSessionUnlock(EsLWSession),
+    NotifyLWSessionLogin(EsLWSession),
+    NotifyLWSessionLogout(EsLWSession),
+}
+
+impl EsRename {
+    fn new(value: &sys::es_event_rename_t) -> Option<Self> {
+        let source = unsafe { value.source.as_ref() }?.into();
+
+        let mut rename_info = Self {
+            source,
+            destination_existing: None,
+            destintaion_newpath: None,
         };
 
-        // SAFETY: message.event_type will (hopefully) never be out of range
-        let eve_type: EsEventType = unsafe { std::mem::transmute(message.event_type) };
-        let process = unsafe { message.process.as_ref().map(|process| process.into()) };
-        let thread_id = unsafe { message.thread.as_ref().map(|tid| tid.thread_id) };
+        if value.destination_type == 0 {
+            rename_info.destination_existing = unsafe {
+                value
+                    .destination
+                    .existing_file
+                    .as_ref()
+                    .map(|file| file.into())
+            };
+        } else {
+            let new_path = unsafe { value.destination.new_path };
+            rename_info.destintaion_newpath = unsafe { new_path.dir.as_ref() }.map(|dir| {
+                (dir.into(), unsafe {
+                    es_string_to_string!(new_path.filename.data)
+                })
+            });
+        }
+
+        Some(rename_info)
+    }
+}
+
+#[derive(Debug)]
+pub struct EsFile {
+    pub path: String,
+    pub path_truncated: bool,
+}
+
+impl From<&sys::es_file_t> for EsFile {
+    fn from(file: &sys::es_file_t) -> Self {
+        let path = unsafe { es_string_to_string!(file.path.data) };
+
+        Self {
+            path,
+            path_truncated: file.path_truncated,
+        }
+    }
+}
+
+bitflags::bitflags! {
+    /// Code signing status of a process, the `CS_*` flags from `<kern/cs_blobs.h>`
+    #[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+    pub struct EsCodesigningFlags: u32 {
+        const VALID = 0x0000_0001;
+        const ADHOC = 0x0000_0002;
+        const GET_TASK_ALLOW = 0x0000_0004;
+        const INSTALLER = 0x0000_0008;
+        const FORCED_LV = 0x0000_0010;
+        const INVALID_ALLOWED = 0x0000_0020;
+        const HARD = 0x0000_0100;
+        const KILL = 0x0000_0200;
+        const CHECK_EXPIRATION = 0x0000_0400;
+        const RESTRICT = 0x0000_0800;
+        const ENFORCEMENT = 0x0000_1000;
+        const REQUIRE_LV = 0x0000_2000;
+        const ENTITLEMENTS_VALIDATED = 0x0000_4000;
+        const NVRAM_UNRESTRICTED = 0x0000_8000;
+        const RUNTIME = 0x0001_0000;
+        const LINKER_SIGNED = 0x0002_0000;
+        const EXEC_SET_HARD = 0x0010_0000;
+        const EXEC_SET_KILL = 0x0020_0000;
+        const EXEC_SET_ENFORCEMENT = 0x0040_0000;
+        const EXEC_INHERIT_SIP = 0x0080_0000;
+        const KILLED = 0x0100_0000;
+        const DYLD_PLATFORM = 0x0200_0000;
+        const PLATFORM_BINARY = 0x0400_0000;
+        const PLATFORM_PATH = 0x0800_0000;
+        const DEBUGGED = 0x1000_0000;
+        const SIGNED = 0x2000_0000;
+        const DEV_CODE = 0x4000_0000;
+        const DATAVAULT_CONTROLLER = 0x8000_0000;
+    }
+}
+
+/// Code directory hash of a process, formats as lowercase hex
+#[derive(Copy, Clone, Eq, Hash, PartialEq)]
+pub struct EsCdHash(pub [u8; 20]);
+
+impl EsCdHash {
+    pub fn as_bytes(&self) -> &[u8; 20] {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for EsCdHash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for byte in self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for EsCdHash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "EsCdHash({})", self)
+    }
+}
+
+/// `secs` and `nanos` since the epoch, `None` if `nanos` isn't a fraction of a second or the time
+/// can't be represented
+fn epoch_time(secs: i64, nanos: i64) -> Option<std::time::SystemTime> {
+    let nanos = Duration::from_nanos(u64::try_from(nanos).ok().filter(|&n| n < 1_000_000_000)?);
+    let time = if secs >= 0 {
+        std::time::UNIX_EPOCH.checked_add(Duration::from_secs(secs as u64))
+    } else {
+        std::time::UNIX_EPOCH.checked_sub(Duration::from_secs(secs.unsigned_abs()))
+    };
+    time?.checked_add(nanos)
+}
+
+/// A process as reported by Endpoint Security.
+///
+/// Fields added in later versions of Endpoint Security are `None` on systems that don't send them.
+#[derive(Debug)]
+pub struct EsProcess {
+    /// process pid
+    pub pid: i32,
+    /// Parent pid
+    pub ppid: i32,
+    /// Parent pid at the time the process was created, unaffected by reparenting
+    pub original_ppid: i32,
+    /// groupd id
+    pub gid: i32,
+    /// session id
+    pub session_id: i32,
+    pub codesigning_flags: EsCodesigningFlags,
+    pub is_platform_binary: bool,
+    /// true if the process is an Endpoint Security client itself
+    pub is_es_client: bool,
+    pub cdhash: EsCdHash,
+    /// Signing identifier, empty for unsigned binaries
+    pub signing_id: String,
+    /// Team identifier, `None` for unsigned and platform binaries
+    pub team_id: Option<String>,
+    pub exe: EsFile,
+    /// controlling terminal
+    pub tty: Option<EsFile>,
+    pub start_time: Option<std::time::SystemTime>,
+    pub audit_token: AuditToken,
+    /// Audit token of the process responsible for this one, e.g. the app that launched a helper
+    pub responsible_audit_token: Option<AuditToken>,
+    /// Audit token of the parent process
+    pub parent_audit_token: Option<AuditToken>,
+}
+
+impl EsProcess {
+    /// Suppresses all events from this process
+    pub fn mute(&self, client: &EsClient) -> Result<(), EsError> {
+        EsError::from_return(client.backend.mute_process(&self.audit_token.into()))
+    }
+
+    fn new(value: &sys::es_process_t, version: u32) -> Self {
+        let audit_token = AuditToken::from(value.audit_token);
+
+        Self {
+            pid: audit_token.pid(),
+            audit_token,
+            ppid: value.ppid,
+            original_ppid: value.original_ppid,
+            gid: value.group_id,
+            session_id: value.session_id,
+            codesigning_flags: EsCodesigningFlags::from_bits_retain(value.codesigning_flags),
+            is_platform_binary: value.is_platform_binary,
+            is_es_client: value.is_es_client,
+            cdhash: EsCdHash(value.cdhash),
+            signing_id: unsafe { es_string_to_opt_string!(value.signing_id.data) }
+                .unwrap_or_default(),
+            team_id: unsafe { es_string_to_opt_string!(value.team_id.data) }
+                .filter(|team_id| !team_id.is_empty()),
+            exe: unsafe { value.executable.as_ref().unwrap().into() },
+            tty: if version >= 2 {
+                unsafe { value.tty.as_ref() }.map(EsFile::from)
+            } else {
+                None
+            },
+            start_time: if version >= 3 {
+                let usecs = i64::from(value.start_time.tv_usec);
+                usecs
+                    .checked_mul(1000)
+                    .filter(|_| usecs < 1_000_000)
+                    .and_then(|nanos| epoch_time(value.start_time.tv_sec, nanos))
+            } else {
+                None
+            },
+            responsible_audit_token: (version >= 4).then(|| value.responsible_audit_token.into()),
+            parent_audit_token: (version >= 4).then(|| value.parent_audit_token.into()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct EsLWSession {
+    pub graphical_session_id: u32,
+    pub username: String,
+}
+
+impl EsLWSession {
+    fn from_es_type(id: u32, str: *const i8) -> Self {
+        Self {
+            graphical_session_id: id,
+            username: unsafe { es_string_to_string!(str) },
+        }
+    }
+}
+
+pub struct EsUser {
+    pub uid: u32,
+    pub username: String,
+}
+
+pub struct EsLogin {
+    pub success: bool,
+    pub err: Option<String>,
+    pub user: EsUser,
+}
+
+impl From<&sys::es_event_login_login_t> for EsLogin {
+    fn from(value: &sys::es_event_login_login_t) -> Self {
+        Self {
+            success: value.success,
+            err: if !value.success {
+                Some(unsafe { es_string_to_string!(value.failure_message.data) })
+            } else {
+                None
+            },
+            user: EsUser {
+                uid: unsafe { value.uid.uid },
+                username: unsafe { es_string_to_string!(value.username.data) },
+            },
+        }
+    }
+}
+
+/// A message delivered to the client, decoded into owned fields.
+///
+/// The underlying `es_message_t` stays retained until the message is dropped. `EsMessage` is
+/// `Send` and `Sync`, so it can be moved to or shared with other threads, but it can't outlive
+/// the client's connection. Use [Self::copy] for a snapshot that can.
+///
+/// [EsMessageRef] is neither `Send` nor `Sync`, it is only valid inside the handler.
+pub struct EsMessage {
+    pub action: EsActionType,
+    pub event: EsEventType,
+    pub event_data: Option<EsEventData>,
+    pub version: u32,
+    pub seq_num: u64,
+    pub process: Option<EsProcess>,
+    pub thread_id: Option<u64>,
+    auth: Option<AuthRequest>,
+    message_ptr: *const sys::es_message_t,
+    /// set if `message_ptr` points into this copy instead of a retained message
+    copy: Option<EsMessageCopy>,
+    backend: Arc<dyn EsBackend>,
+}
+
+// SAFETY: ES messages are reference counted by the subsystem, retaining, releasing and
+// responding to them is allowed from any thread.
+unsafe impl Send for EsMessage {}
+// SAFETY: the retained message is only ever read, answering requires `&mut self`
+unsafe impl Sync for EsMessage {}
+
+impl std::fmt::Debug for EsMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EsMessage")
+            .field("action", &self.action)
+            .field("event", &self.event)
+            .field("event_data", &self.event_data)
+            .field("version", &self.version)
+            .field("seq_num", &self.seq_num)
+            .field("process", &self.process)
+            .field("thread_id", &self.thread_id)
+            .field("auth", &self.auth)
+            .field("message_ptr", &self.message_ptr)
+            .finish()
+    }
+}
+
+impl EsMessage {
+    /// Take the pending response of an auth event, `None` for notify events or if it was already
+    /// taken.
+    ///
+    /// If the request isn't taken, dropping the message answers it with the client's
+    /// [default](EsClient::set_auth_default).
+    pub fn take_auth(&mut self) -> Option<AuthRequest> {
+        self.auth.take()
+    }
+
+    /// Copy the message into an [EsMessageCopy] that is independent of the client
+    pub fn copy(&self) -> Result<EsMessageCopy, EsError> {
+        EsMessageCopy::new(self.raw(), self.backend.clone())
+    }
+
+    fn raw(&self) -> &sys::es_message_t {
+        // SAFETY: the message is retained or copied for as long as `self` lives
+        unsafe { &*self.message_ptr }
+    }
+
+    /// Decode `message`, creating the [AuthRequest] of auth events from `auth` if given
+    fn new(
+        message: &sys::es_message_t,
+        backend: Arc<dyn EsBackend>,
+        auth: Option<&AuthSettings>,
+    ) -> Self {
+        backend.retain_message(message);
+        Self::decode(message, backend, auth, None)
+    }
+
+    /// Decode a message copy, which is kept instead of retained
+    fn from_copy(copy: EsMessageCopy) -> Self {
+        let message: *const sys::es_message_t = copy.raw();
+        let backend = copy.backend().clone();
+        // SAFETY: the copy is moved into the message without moving the data it points to
+        Self::decode(unsafe { &*message }, backend, None, Some(copy))
+    }
+
+    fn decode(
+        message: &sys::es_message_t,
+        backend: Arc<dyn EsBackend>,
+        auth: Option<&AuthSettings>,
+        copy: Option<EsMessageCopy>,
+    ) -> Self {
+        let action = EsActionType::from_raw(message.action_type);
+
+        let eve_type = EsEventType::from_raw(message.event_type);
+        let process = unsafe { message.process.as_ref() }
+            .map(|process| EsProcess::new(process, message.version));
+        let thread_id = unsafe { message.thread.as_ref().map(|tid| tid.thread_id) };
+
+        let eve = EsEventData::from_message(message, eve_type, backend.as_ref());
+
+        let auth = auth
+            .filter(|_| action == EsActionType::Auth)
+            .map(|auth| auth.request(message, backend.clone(), eve_type));
+
+        Self {
+            event: eve_type,
+            event_data: eve,
+            version: message.version,
+            seq_num: message.seq_num,
+            message_ptr: message as _,
+            action,
+            process,
+            thread_id,
+            auth,
+            copy,
+            backend,
+        }
+    }
+}
+
+impl Drop for EsMessage {
+    fn drop(&mut self) {
+        // copies are freed on their own
+        if self.copy.is_none() {
+            self.backend.release_message(self.raw());
+        }
+    }
+}
 
+impl EsEventData {
+    fn from_message(
+        message: &sys::es_message_t,
+        event: EsEventType,
+        backend: &dyn EsBackend,
+    ) -> Option<Self> {
         // unsafe { message.event.screensharing_attach.as_ref().unwrap() }
 
-        let eve = match eve_type {
-            EsEventType::AuthOpen => unsafe {
-                message
-                    .event
-                    .open
-                    .file
-                    .as_ref()
-                    .map(|file| EsEventData::AuthOpen(file.into()))
-            },
-            EsEventType::AuthRename => Some(EsEventData::AuthRename(unsafe {
-                message.event.rename.into()
-            })),
-            EsEventType::AuthUnlink => Some(EsEventData::AuthUnlink(unsafe {
-                message.event.unlink.into()
-            })),
+        match event {
+            EsEventType::AuthOpen => {
+                EsOpen::new(unsafe { &message.event.open }).map(EsEventData::AuthOpen)
+            }
+            EsEventType::AuthRename => unsafe {
+                EsRename::new(&message.event.rename).map(EsEventData::AuthRename)
+            },
+            EsEventType::AuthUnlink => unsafe {
+                EsUnlinkFile::new(&message.event.unlink).map(EsEventData::AuthUnlink)
+            },
             EsEventType::AuthReadDir => unsafe {
                 message
                     .event
@@ -765,22 +1938,17 @@ impl From<&sys::es_message_t> for EsMessage {
                     .as_ref()
                     .map(|chroot| EsEventData::NotifyChroot(chroot.into()))
             },
-            EsEventType::NotifyExec => unsafe {
-                message
-                    .event
-                    .exec
-                    .target
-                    .as_ref()
-                    .map(|tar| EsEventData::NotifyExec(tar.into()))
+            EsEventType::AuthExec => unsafe {
+                EsExec::new(&message.event.exec, message.version, backend)
+                    .map(EsEventData::AuthExec)
             },
-            EsEventType::NotifyOpen => unsafe {
-                message
-                    .event
-                    .open
-                    .file
-                    .as_ref()
-                    .map(|file| EsEventData::NotifyOpen(file.into()))
+            EsEventType::NotifyExec => unsafe {
+                EsExec::new(&message.event.exec, message.version, backend)
+                    .map(EsEventData::NotifyExec)
             },
+            EsEventType::NotifyOpen => {
+                EsOpen::new(unsafe { &message.event.open }).map(EsEventData::NotifyOpen)
+            }
             EsEventType::NotifyWrite => unsafe {
                 message
                     .event
@@ -789,13 +1957,16 @@ impl From<&sys::es_message_t> for EsMessage {
                     .as_ref()
                     .map(|target| EsEventData::NotifyWrite(target.into()))
             },
-            EsEventType::NotifyRename => Some(EsEventData::NotifyRename(unsafe {
-                message.event.rename.into()
-            })),
-            EsEventType::NotifyClose => Some(EsEventData::NotifyClose((
-                unsafe { message.event.close.target.as_ref().unwrap().into() },
-                unsafe { message.event.close.modified },
-            ))),
+            EsEventType::NotifyRename => unsafe {
+                EsRename::new(&message.event.rename).map(EsEventData::NotifyRename)
+            },
+            EsEventType::NotifyClose => unsafe {
+                let close = message.event.close;
+                close
+                    .target
+                    .as_ref()
+                    .map(|target| EsEventData::NotifyClose((target.into(), close.modified)))
+            },
             EsEventType::NotifyReadDir => unsafe {
                 message
                     .event
@@ -857,48 +2028,209 @@ impl From<&sys::es_message_t> for EsMessage {
                 Some(EsEventData::NotifyCopyFile(message.event.copyfile.into()))
             },
             EsEventType::NotifyClone => unsafe {
-                Some(EsEventData::AuthClone(message.event.clone.into()))
+                EsClone::new(&message.event.clone).map(EsEventData::AuthClone)
             },
             EsEventType::AuthClone => unsafe {
-                Some(EsEventData::AuthClone(message.event.clone.into()))
+                EsClone::new(&message.event.clone).map(EsEventData::AuthClone)
             },
             EsEventType::NotifyMMap => unsafe {
-                Some(EsEventData::NotifyMMap(message.event.mmap.into()))
+                EsMMap::new(&message.event.mmap).map(EsEventData::NotifyMMap)
             },
             EsEventType::AuthMMap => unsafe {
-                Some(EsEventData::AuthMMap(message.event.mmap.into()))
+                EsMMap::new(&message.event.mmap).map(EsEventData::AuthMMap)
             },
             EsEventType::NotifyExit => unsafe {
-                Some(EsEventData::NotifyExit(message.event.exit.stat))
+                Some(EsEventData::NotifyExit((&message.event.exit).into()))
+            },
+            EsEventType::AuthSetMode => unsafe {
+                EsSetMode::new(&message.event.setmode).map(EsEventData::AuthSetMode)
+            },
+            EsEventType::NotifySetMode => unsafe {
+                EsSetMode::new(&message.event.setmode).map(EsEventData::NotifySetMode)
+            },
+            EsEventType::AuthSetFlags => unsafe {
+                EsSetFlags::new(&message.event.setflags).map(EsEventData::AuthSetFlags)
+            },
+            EsEventType::NotifySetFlags => unsafe {
+                EsSetFlags::new(&message.event.setflags).map(EsEventData::NotifySetFlags)
+            },
+            EsEventType::AuthSetOwner => unsafe {
+                EsSetOwner::new(&message.event.setowner).map(EsEventData::AuthSetOwner)
+            },
+            EsEventType::NotifySetOwner => unsafe {
+                EsSetOwner::new(&message.event.setowner).map(EsEventData::NotifySetOwner)
+            },
+            EsEventType::AuthSetACL => unsafe {
+                EsSetAcl::new(&message.event.setacl, backend).map(EsEventData::AuthSetACL)
+            },
+            EsEventType::NotifySetACL => unsafe {
+                EsSetAcl::new(&message.event.setacl, backend).map(EsEventData::NotifySetACL)
+            },
+            EsEventType::AuthUtimes => unsafe {
+                EsUtimes::new(&message.event.utimes).map(EsEventData::AuthUtimes)
+            },
+            EsEventType::NotifyUtimes => unsafe {
+                EsUtimes::new(&message.event.utimes).map(EsEventData::NotifyUtimes)
+            },
+            EsEventType::AuthTruncate => unsafe {
+                message
+                    .event
+                    .truncate
+                    .target
+                    .as_ref()
+                    .map(|target| EsEventData::AuthTruncate(target.into()))
+            },
+            EsEventType::NotifyTruncate => unsafe {
+                message
+                    .event
+                    .truncate
+                    .target
+                    .as_ref()
+                    .map(|target| EsEventData::NotifyTruncate(target.into()))
+            },
+            EsEventType::AuthSetExtAttr => unsafe {
+                let event = &message.event.setextattr;
+                EsExtAttr::new(event.target, &event.extattr).map(EsEventData::AuthSetExtAttr)
+            },
+            EsEventType::NotifySetExtAttr => unsafe {
+                let event = &message.event.setextattr;
+                EsExtAttr::new(event.target, &event.extattr).map(EsEventData::NotifySetExtAttr)
+            },
+            EsEventType::AuthGetExtAttr => unsafe {
+                let event = &message.event.getextattr;
+                EsExtAttr::new(event.target, &event.extattr).map(EsEventData::AuthGetExtAttr)
+            },
+            EsEventType::NotifyGetExtAttr => unsafe {
+                let event = &message.event.getextattr;
+                EsExtAttr::new(event.target, &event.extattr).map(EsEventData::NotifyGetExtAttr)
+            },
+            EsEventType::AuthDeleteExtAttr => unsafe {
+                let event = &message.event.deleteextattr;
+                EsExtAttr::new(event.target, &event.extattr).map(EsEventData::AuthDeleteExtAttr)
+            },
+            EsEventType::NotifyDeleteExtAttr => unsafe {
+                let event = &message.event.deleteextattr;
+                EsExtAttr::new(event.target, &event.extattr).map(EsEventData::NotifyDeleteExtAttr)
+            },
+            EsEventType::AuthListenExtAttr => unsafe {
+                message
+                    .event
+                    .listextattr
+                    .target
+                    .as_ref()
+                    .map(|target| EsEventData::AuthListenExtAttr(target.into()))
+            },
+            EsEventType::NotifyListenExtAttr => unsafe {
+                message
+                    .event
+                    .listextattr
+                    .target
+                    .as_ref()
+                    .map(|target| EsEventData::NotifyListenExtAttr(target.into()))
+            },
+            EsEventType::AuthMount => unsafe {
+                EsMount::new(message.event.mount.statfs).map(EsEventData::AuthMount)
+            },
+            EsEventType::NotifyMount => unsafe {
+                EsMount::new(message.event.mount.statfs).map(EsEventData::NotifyMount)
+            },
+            EsEventType::NotifyUnmount => unsafe {
+                EsMount::new(message.event.unmount.statfs).map(EsEventData::NotifyUnmount)
+            },
+            EsEventType::AuthRemount => unsafe {
+                EsMount::new(message.event.remount.statfs).map(EsEventData::AuthRemount)
+            },
+            EsEventType::NotifyRemount => unsafe {
+                EsMount::new(message.event.remount.statfs).map(EsEventData::NotifyRemount)
+            },
+            EsEventType::NotifyFork => unsafe {
+                EsFork::new(&message.event.fork, message.version).map(EsEventData::NotifyFork)
+            },
+            EsEventType::AuthSignal => unsafe {
+                EsSignal::new(&message.event.signal, message.version).map(EsEventData::AuthSignal)
+            },
+            EsEventType::NotifySignal => unsafe {
+                EsSignal::new(&message.event.signal, message.version).map(EsEventData::NotifySignal)
+            },
+            EsEventType::AuthProcCheck => unsafe {
+                Some(EsEventData::AuthProcCheck(EsProcCheck::new(
+                    &message.event.proc_check,
+                    message.version,
+                )))
+            },
+            EsEventType::NotifyProcCheck => unsafe {
+                Some(EsEventData::NotifyProcCheck(EsProcCheck::new(
+                    &message.event.proc_check,
+                    message.version,
+                )))
+            },
+            EsEventType::AuthProcSuspendResume => unsafe {
+                Some(EsEventData::AuthProcSuspendResume(
+                    EsProcSuspendResume::new(&message.event.proc_suspend_resume, message.version),
+                ))
+            },
+            EsEventType::AuthGetTask => unsafe {
+                let task = message.event.get_task;
+                EsGetTask::new(task.target, task.type_, message.version)
+                    .map(EsEventData::AuthGetTask)
+            },
+            EsEventType::NotifyGetTask => unsafe {
+                let task = message.event.get_task;
+                EsGetTask::new(task.target, task.type_, message.version)
+                    .map(EsEventData::NotifyGetTask)
+            },
+            EsEventType::AuthGetTaskRead => unsafe {
+                let task = message.event.get_task_read;
+                EsGetTask::new(task.target, task.type_, message.version)
+                    .map(EsEventData::AuthGetTaskRead)
+            },
+            EsEventType::NotifyGetTaskRead => unsafe {
+                let task = message.event.get_task_read;
+                EsGetTask::new(task.target, task.type_, message.version)
+                    .map(EsEventData::NotifyGetTaskRead)
+            },
+            EsEventType::NotifyGetTaskInspect => unsafe {
+                let task = message.event.get_task_inspect;
+                EsGetTask::new(task.target, task.type_, message.version)
+                    .map(EsEventData::NotifyGetTaskInspect)
+            },
+            EsEventType::NotifyGetTaskName => unsafe {
+                let task = message.event.get_task_name;
+                EsGetTask::new(task.target, task.type_, message.version)
+                    .map(EsEventData::NotifyGetTaskName)
+            },
+            EsEventType::NotfiyTrace => unsafe {
+                EsTrace::new(&message.event.trace, message.version).map(EsEventData::NotfiyTrace)
+            },
+            EsEventType::NotifyRemoteThreadCreate => unsafe {
+                EsRemoteThreadCreate::new(&message.event.remote_thread_create, message.version)
+                    .map(EsEventData::NotifyRemoteThreadCreate)
+            },
+            EsEventType::NotifyProcSuspendResume => unsafe {
+                Some(EsEventData::NotifyProcSuspendResume(
+                    EsProcSuspendResume::new(&message.event.proc_suspend_resume, message.version),
+                ))
             },
             _ => None,
-        };
-
-        unsafe { sys::es_retain_message(message as _) }
-
-        Self {
-            event: eve_type,
-            event_data: eve,
-            version: message.version,
-            seq_num: message.seq_num,
-            message_ptr: message as _,
-            action,
-            process,
-            thread_id,
         }
     }
 }
 
+fn path_to_cstring(path: &Path) -> Result<CString, EsError> {
+    CString::new(path.as_os_str().as_encoded_bytes()).map_err(|_| EsError::InvalidArgument)
+}
+
 /// Create a new client to connect to Endpoint Security.
 pub struct EsClient {
-    client: *mut sys::es_client_t,
+    backend: Arc<dyn EsBackend>,
     subscribed_events: Vec<EsEventType>,
-    pub rx: crossbeam::channel::Receiver<EsMessage>,
+    auth: Arc<AuthSettings>,
+    watchdog: Option<JoinHandle<()>>,
+    deleted: bool,
+    channel: Arc<Channel>,
 }
 
 impl EsClient {
-    extern "C" fn handler(_c: *mut sys::es_client_t, _m: *const sys::es_message_t) {}
-
     /// Create a new client that connects to the ES subsystem.
     ///
     /// # Example
@@ -906,40 +2238,92 @@ impl EsClient {
     ///     let client = endpointsecurity_rs::EsClient::new();
     ///     assert!(client.is_ok());
     /// ```
-    pub fn new() -> anyhow::Result<EsClient> {
-        let mut client: *mut sys::es_client_t = std::ptr::null_mut();
+    #[cfg(target_os = "macos")]
+    pub fn new() -> Result<EsClient, EsError> {
+        Self::with_backend(Arc::new(SystemBackend::default()))
+    }
+
+    /// Create a new client that receives its messages from `backend`.
+    ///
+    /// Use a [SimulatedBackend] to run a client without the ES subsystem.
+    pub fn with_backend(backend: Arc<dyn EsBackend>) -> Result<EsClient, EsError> {
+        Self::with_channel(backend, Channel::unbounded())
+    }
 
-        let (tx, rx) = channel::unbounded();
+    /// Like [Self::with_backend], but at most `capacity` messages are queued. Messages arriving
+    /// while the queue is full are handled according to `policy` and counted in
+    /// [Self::channel_stats].
+    ///
+    /// Fails with [EsError::InvalidArgument] if `capacity` is 0.
+    pub fn with_backend_bounded(
+        backend: Arc<dyn EsBackend>,
+        capacity: usize,
+        policy: OverflowPolicy,
+    ) -> Result<EsClient, EsError> {
+        if capacity == 0 {
+            return Err(EsError::InvalidArgument);
+        }
+        Self::with_channel(backend, Channel::bounded(capacity, policy))
+    }
 
-        let handler = ConcreteBlock::new(move |c, msg: *const sys::es_message_t| {
-            let message = unsafe { msg.as_ref() };
-            if message.is_none() {
-                println!("Failed to get message reference");
-                return;
-            }
-            let message = message.unwrap();
-            assert!(msg as usize == message as *const _ as usize);
+    fn with_channel(
+        backend: Arc<dyn EsBackend>,
+        channel: Arc<Channel>,
+    ) -> Result<EsClient, EsError> {
+        let tx = MessageSender::new(channel.clone());
+        Self::with_delivery(backend, channel, move |message| {
+            tx.send(message.into_message())
+        })
+    }
 
-            _ = tx.send(message.into());
+    /// Client that runs `callback` inside the ES handler, nothing is queued for
+    /// [Self::recv_msg]
+    fn with_callback(
+        backend: Arc<dyn EsBackend>,
+        callback: MessageCallback,
+    ) -> Result<EsClient, EsError> {
+        Self::with_delivery(backend, Channel::disconnected(), move |message| {
+            callback::dispatch(&callback, message.into_message())
+        })
+    }
 
-            // this call is just to infer the types in the closure
-            Self::handler(c, msg);
+    /// Like [Self::with_callback], passing borrowed messages
+    fn with_callback_ref(
+        backend: Arc<dyn EsBackend>,
+        callback: MessageRefCallback,
+    ) -> Result<EsClient, EsError> {
+        Self::with_delivery(backend, Channel::disconnected(), move |message| {
+            callback::dispatch_ref(&callback, message)
         })
-        .copy();
-
-        if let Some(err) = EsClientCreateError::from_u32(unsafe {
-            sys::es_new_client(
-                &mut client as _,
-                &*handler as *const block::Block<_, _> as *mut std::ffi::c_void,
-            )
-        }) {
-            anyhow::bail!(err)
+    }
+
+    fn with_delivery(
+        backend: Arc<dyn EsBackend>,
+        channel: Arc<Channel>,
+        deliver: impl Fn(EsMessageRef<'_>) + Send + Sync + 'static,
+    ) -> Result<EsClient, EsError> {
+        let auth = Arc::new(AuthSettings::default());
+
+        let weak_backend: Weak<dyn EsBackend> = Arc::downgrade(&backend);
+        let handler_auth = auth.clone();
+        let handler = move |message: &sys::es_message_t| {
+            // the backend is gone once the client is dropped, nobody is listening anymore
+            if let Some(backend) = weak_backend.upgrade() {
+                deliver(EsMessageRef::new(message, &backend, Some(&handler_auth)));
+            }
+        };
+
+        if let Some(err) = EsClientCreateError::from_u32(backend.new_client(Box::new(handler))) {
+            return Err(err.into());
         }
 
         Ok(EsClient {
-            client,
+            backend,
             subscribed_events: vec![],
-            rx,
+            auth,
+            watchdog: None,
+            deleted: false,
+            channel,
         })
     }
 
@@ -952,122 +2336,297 @@ impl EsClient {
     }
 
     /// Subscribe to all the events added using [Self::add_event]
-    pub fn subscribe(&self) {
+    pub fn subscribe(&self) -> Result<(), EsError> {
         let mut event_ids = vec![];
         for evt in &self.subscribed_events {
-            event_ids.push(*evt as sys::es_event_type_t)
+            event_ids.push(evt.raw())
         }
 
-        if unsafe {
-            sys::es_subscribe(self.client as _, event_ids.as_ptr(), event_ids.len() as u32)
-        } != 0
-        {
-            panic!("Error: Failed to subscribe");
-        }
+        EsError::from_return(self.backend.subscribe(&event_ids))
     }
 
-    /// returns true if call to unsubscribe is successful, otherwise false
-    pub fn unsubscribe_all(&self) -> bool {
-        (unsafe { sys::es_unsubscribe_all(self.client) } == 0)
+    /// Unsubscribe from all events
+    pub fn unsubscribe_all(&self) -> Result<(), EsError> {
+        EsError::from_return(self.backend.unsubscribe_all())
     }
 
-    /// returns true if call to unsubscribe is successful, otherwise false/
-    pub fn unsubscribe(&mut self, event: EsEventType) -> bool {
+    /// Unsubscribe from an event added using [Self::add_event]
+    pub fn unsubscribe(&mut self, event: EsEventType) -> Result<(), EsError> {
         if let Some(idx) = self.subscribed_events.iter().position(|eve| *eve == event) {
             self.subscribed_events.swap_remove(idx);
         } else {
-            return false;
+            return Err(EsError::NotSubscribed(event));
         }
 
-        let events = vec![event as u32];
+        EsError::from_return(self.backend.unsubscribe(&[event.raw()]))
+    }
 
-        (unsafe { sys::es_unsubscribe(self.client, events.as_ptr(), events.len() as u32) } == 0)
+    /// Get the events that the user subscribed to
+    pub fn subscriptions(&self) -> Result<Vec<EsEventType>, EsError> {
+        let events = self.backend.subscriptions().ok_or(EsError::Failed)?;
+        Ok(events.into_iter().map(EsEventType::from_raw).collect())
     }
 
-    /// Get the events that the user subscribed to. Returns `None` on error
-    pub fn subscriptions(&self) -> Option<Vec<EsEventType>> {
-        let mut count = 0;
-        let mut eves: *mut EsEventType = core::ptr::null_mut();
-        if unsafe {
-            sys::es_subscriptions(
-                self.client,
-                &mut count,
-                &mut eves as *mut *mut _ as *mut *mut u32,
-            )
-        } != 0
-        {
-            None
-        } else {
-            let events = unsafe { std::slice::from_raw_parts(eves, count) }.to_vec();
+    /// Decision sent for auth events whose [AuthRequest] is dropped without an answer, applies to
+    /// messages received from now on. Defaults to [AuthDecision::Allow].
+    pub fn set_auth_default(&self, decision: AuthDecision) {
+        self.auth.set_default(decision);
+    }
 
-            // im not sure if this is the correct way to free
-            extern "C" {
-                fn free(ptr: *mut std::ffi::c_void);
-            }
-            unsafe { free(eves as _) };
+    /// Start a thread that answers auth events with `decision` once they are still pending
+    /// `margin` before their deadline, replacing a running one.
+    ///
+    /// Only messages received after the watchdog started are tracked. `on_forced` is called
+    /// from the watchdog thread for every response it sends.
+    ///
+    /// Fails with [EsError::ThreadSpawn] if the thread couldn't be started, a running watchdog is
+    /// kept in that case.
+    pub fn start_auth_watchdog(
+        &mut self,
+        margin: Duration,
+        decision: AuthDecision,
+        on_forced: Option<ForcedAuthCallback>,
+    ) -> Result<(), EsError> {
+        let forced = self.auth.forced().clone();
+        let (watchdog, handle) = watchdog::Watchdog::spawn(margin, decision, on_forced, forced)?;
+
+        self.stop_auth_watchdog();
+        self.auth.set_watchdog(Some(watchdog));
+        self.watchdog = Some(handle);
+        Ok(())
+    }
 
-            Some(events)
+    /// Stop the watchdog started with [Self::start_auth_watchdog], if any
+    pub fn stop_auth_watchdog(&mut self) {
+        if let Some(watchdog) = self.auth.set_watchdog(None) {
+            watchdog.stop();
         }
+        if let Some(handle) = self.watchdog.take() {
+            _ = handle.join();
+        }
+    }
+
+    /// Drop all auth results cached by the kernel for every client, so that the next event of
+    /// each kind is sent for evaluation again.
+    ///
+    /// Fails with [EsError::Throttled] if called too often.
+    pub fn clear_cache(&self) -> Result<(), EsError> {
+        EsError::from_clear_cache_result(self.backend.clear_cache())
+    }
+
+    /// Run `reload` to update the client's policy and clear the cache afterwards, so results
+    /// cached under the old policy stop applying.
+    ///
+    /// Returns the value of `reload` together with the result of clearing the cache. The policy
+    /// is reloaded either way, if the clear was [throttled](EsError::Throttled) retry it with
+    /// [Self::clear_cache].
+    pub fn reload_policy<T>(&self, reload: impl FnOnce() -> T) -> (T, Result<(), EsError>) {
+        let value = reload();
+        (value, self.clear_cache())
+    }
+
+    /// Number of auth events the watchdog answered on behalf of the client
+    pub fn forced_auth_responses(&self) -> u64 {
+        self.auth.forced().load(Ordering::Relaxed)
+    }
+
+    /// Messages a bounded client didn't deliver so far, see [Self::with_backend_bounded]
+    pub fn channel_stats(&self) -> ChannelStats {
+        self.channel.stats()
     }
 
     /// This function blocks
-    pub fn recv_msg(&self) -> Result<EsMessage, channel::RecvError> {
-        self.rx.recv()
+    pub fn recv_msg(&self) -> Result<EsMessage, EsError> {
+        self.channel.recv()
+    }
+
+    /// This function doesn't block, returns `None` if no message is pending
+    pub fn try_recv_msg(&self) -> Result<Option<EsMessage>, EsError> {
+        self.channel.try_recv()
+    }
+
+    /// Poll for the next message, the task is woken once one arrives or the client is
+    /// disconnected
+    pub fn poll_recv(&self, cx: &mut Context<'_>) -> Poll<Result<EsMessage, EsError>> {
+        match self.channel.try_recv_or_register(Some(cx.waker())) {
+            Ok(Some(msg)) => Poll::Ready(Ok(msg)),
+            Ok(None) => Poll::Pending,
+            Err(err) => Poll::Ready(Err(err)),
+        }
+    }
+
+    /// Wait for the next message without blocking the thread.
+    ///
+    /// Cancel safe, a message is never lost if the future is dropped.
+    pub fn recv(&self) -> Recv<'_> {
+        Recv { client: self }
+    }
+
+    /// All further messages as a [Stream](futures_core::Stream)
+    #[cfg(feature = "async")]
+    pub fn messages(&self) -> EsMessageStream<'_> {
+        EsMessageStream { client: self }
+    }
+
+    /// Suppresses all events from the process identified by `audit_token`.
+    pub fn mute_process(&self, audit_token: AuditToken) -> Result<(), EsError> {
+        EsError::from_return(self.backend.mute_process(&audit_token.into()))
+    }
+
+    /// Suppresses a subset of events from the process identified by `audit_token`.
+    pub fn mute_process_events(
+        &self,
+        audit_token: AuditToken,
+        events: &[EsEventType],
+    ) -> Result<(), EsError> {
+        let events: Vec<_> = events.iter().map(|event| event.raw()).collect();
+
+        EsError::from_return(
+            self.backend
+                .mute_process_events(&audit_token.into(), &events),
+        )
+    }
+
+    /// Restores event delivery from a previously-muted process.
+    pub fn unmute_process(&self, audit_token: AuditToken) -> Result<(), EsError> {
+        EsError::from_return(self.backend.unmute_process(&audit_token.into()))
+    }
+
+    /// Restores event delivery of a subset of events from a previously-muted process.
+    pub fn unmute_process_events(
+        &self,
+        audit_token: AuditToken,
+        events: &[EsEventType],
+    ) -> Result<(), EsError> {
+        let events: Vec<_> = events.iter().map(|event| event.raw()).collect();
+
+        EsError::from_return(
+            self.backend
+                .unmute_process_events(&audit_token.into(), &events),
+        )
+    }
+
+    /// Get the muted processes and the events muted for each of them.
+    ///
+    /// The list is copied out of the ES subsystem, which is then released with
+    /// `es_release_muted_processes`.
+    pub fn muted_processes_events(&self) -> Result<Vec<(AuditToken, Vec<EsEventType>)>, EsError> {
+        let muted = self
+            .backend
+            .muted_processes_events()
+            .ok_or(EsError::Failed)?;
+
+        Ok(muted
+            .into_iter()
+            .map(|(audit_token, events)| {
+                let events = events.into_iter().map(EsEventType::from_raw).collect();
+                (audit_token.into(), events)
+            })
+            .collect())
     }
 
-    /// This function doesn't block
-    pub fn try_recv_msg(&self) -> Result<EsMessage, channel::TryRecvError> {
-        self.rx.try_recv()
+    /// Suppresses events from executables or targets that match a given path.
+    ///
+    /// Fails with [EsError::InvalidArgument] if `path` contains a NUL byte.
+    pub fn mute_path(&self, path: &Path, ty: EsMutePath) -> Result<(), EsError> {
+        let path = path_to_cstring(path)?;
+        EsError::from_return(self.backend.mute_path(&path, ty.raw()))
     }
 
-    /// Suppresses events from executables that match a given path.
-    /// Returns `true` if muting was succesful.
-    pub fn mute_path(&self, path: &std::path::Path, ty: EsMutePath) -> bool {
-        (unsafe { sys::es_mute_path(self.client, path.to_string_lossy().as_ptr() as _, ty as u32) }
-            == 0)
+    /// Suppresses a subset of events from executables or targets that match a given path.
+    pub fn mute_path_events(
+        &self,
+        path: &Path,
+        ty: EsMutePath,
+        events: &[EsEventType],
+    ) -> Result<(), EsError> {
+        let path = path_to_cstring(path)?;
+        let events: Vec<_> = events.iter().map(|event| event.raw()).collect();
+
+        EsError::from_return(self.backend.mute_path_events(&path, ty.raw(), &events))
     }
 
     /// Restores event delivery from a previously-muted path.
-    /// Returns `true` if muting was succesful.
-    pub fn unmute_path(&self, path: &std::path::Path, ty: EsMutePath) -> bool {
-        (unsafe {
-            sys::es_unmute_path(self.client, path.to_string_lossy().as_ptr() as _, ty as u32)
-        } == 0)
+    pub fn unmute_path(&self, path: &Path, ty: EsMutePath) -> Result<(), EsError> {
+        let path = path_to_cstring(path)?;
+        EsError::from_return(self.backend.unmute_path(&path, ty.raw()))
     }
 
     /// Restores event delivery of a subset of events from a previously-muted path.
     pub fn unmute_path_events(
         &self,
-        path: &std::path::Path,
+        path: &Path,
         ty: EsMutePath,
         events: &[EsEventType],
-    ) -> bool {
-        let events: Vec<_> = events.iter().map(|event| *event as u32).collect();
+    ) -> Result<(), EsError> {
+        let path = path_to_cstring(path)?;
+        let events: Vec<_> = events.iter().map(|event| event.raw()).collect();
+
+        EsError::from_return(self.backend.unmute_path_events(&path, ty.raw(), &events))
+    }
+
+    /// Restores event delivery from previously-muted executable paths.
+    pub fn unmute_all_paths(&self) -> Result<(), EsError> {
+        EsError::from_return(self.backend.unmute_all_paths())
+    }
+
+    /// Restores event delivery from previously-muted target paths.
+    pub fn unmute_all_target_paths(&self) -> Result<(), EsError> {
+        EsError::from_return(self.backend.unmute_all_target_paths())
+    }
+
+    /// Get the muted paths and the events muted for each of them.
+    pub fn muted_paths_events(&self) -> Result<Vec<EsMutedPath>, EsError> {
+        let muted = self.backend.muted_paths_events().ok_or(EsError::Failed)?;
+
+        Ok(muted
+            .into_iter()
+            .map(|(path, ty, events)| EsMutedPath {
+                path: message_ref::c_path(&path).to_path_buf(),
+                ty: EsMutePath::from_raw(ty),
+                events: events.into_iter().map(EsEventType::from_raw).collect(),
+            })
+            .collect())
+    }
 
-        (unsafe {
-            sys::es_unmute_path_events(
-                self.client,
-                path.to_string_lossy().as_ptr() as _,
-                ty as u32,
-                events.as_ptr(),
-                events.len(),
-            )
-        } == 0)
+    /// Invert the meaning of muting for `ty`: once inverted, only events that would be muted
+    /// are delivered. Calling it again restores normal muting.
+    ///
+    /// E.g. invert [EsMuteInversion::TargetPath] and mute `/Users/foo/Documents` with
+    /// [EsMutePath::TargetPrefix] to only watch files in that directory.
+    pub fn invert_muting(&self, ty: EsMuteInversion) -> Result<(), EsError> {
+        EsError::from_return(self.backend.invert_muting(ty as u32))
     }
 
-    /// Restores event delivery from previously-muted paths.
-    pub fn unmute_all_paths(&self) -> bool {
-        (unsafe { sys::es_unmute_all_paths(self.client) } == 0)
+    /// Whether muting is inverted for `ty`
+    pub fn muting_inverted(&self, ty: EsMuteInversion) -> Result<bool, EsError> {
+        match self.backend.muting_inverted(ty as u32) {
+            sys::es_mute_inverted_return_t_ES_MUTE_INVERTED => Ok(true),
+            sys::es_mute_inverted_return_t_ES_MUTE_NOT_INVERTED => Ok(false),
+            _ => Err(EsError::Failed),
+        }
     }
 
     /// Deletes the client
-    pub fn destroy_client(self) {}
+    pub fn destroy_client(mut self) -> Result<(), EsError> {
+        self.delete()
+    }
+
+    fn delete(&mut self) -> Result<(), EsError> {
+        if std::mem::replace(&mut self.deleted, true) {
+            return Ok(());
+        }
+        // a handler waiting for room must not hold up the disconnect
+        self.channel.close();
+        self.stop_auth_watchdog();
+        EsError::from_return(self.backend.delete_client())
+    }
 }
 
 impl Drop for EsClient {
     fn drop(&mut self) {
-        if unsafe { sys::es_delete_client(self.client) } != 0 {
-            println!("Failed to delete client");
+        if let Err(err) = self.delete() {
+            println!("Failed to delete client: {}", err);
         }
     }
 }
@@ -1076,8 +2635,429 @@ impl Drop for EsClient {
 mod tests {
 
     #[test]
+    #[cfg(target_os = "macos")]
     pub fn test_new_es_client() {
         let client = crate::EsClient::new();
         assert!(client.is_ok());
     }
+
+    fn es_file(path: &'static std::ffi::CStr) -> crate::sys::es_file_t {
+        let mut file: crate::sys::es_file_t = unsafe { std::mem::zeroed() };
+        file.path = crate::sys::es_string_token_t {
+            length: path.to_bytes().len(),
+            data: path.as_ptr(),
+        };
+        file
+    }
+
+    #[test]
+    pub fn test_exec_fields_follow_message_version() {
+        use crate::{sys, EsExec, SimulatedBackend};
+
+        let mut executable = es_file(c"/bin/sh");
+        let mut script = es_file(c"/tmp/script.sh");
+        let mut process: sys::es_process_t = unsafe { std::mem::zeroed() };
+        process.executable = &mut executable;
+
+        let mut exec: sys::es_event_exec_t = unsafe { std::mem::zeroed() };
+        exec.target = &mut process;
+        exec.__bindgen_anon_1.__bindgen_anon_1 = sys::es_event_exec_t__bindgen_ty_1__bindgen_ty_1 {
+            script: &mut script,
+            cwd: std::ptr::null_mut(),
+            last_fd: 3,
+            image_cputype: 7,
+            image_cpusubtype: 3,
+        };
+
+        let backend = SimulatedBackend::new();
+        let old = EsExec::new(&exec, 1, &backend).unwrap();
+        assert_eq!(old.target.exe.path, "/bin/sh");
+        assert!(old.script.is_none());
+        assert_eq!(old.last_fd, None);
+
+        let new = EsExec::new(&exec, 6, &backend).unwrap();
+        assert_eq!(new.script.unwrap().path, "/tmp/script.sh");
+        assert!(new.cwd.is_none());
+        assert_eq!(new.last_fd, Some(3));
+        assert_eq!(new.image_cputype, Some(7));
+
+        exec.target = std::ptr::null_mut();
+        assert!(EsExec::new(&exec, 6, &backend).is_none());
+    }
+
+    #[test]
+    pub fn test_process_signing_info() {
+        use crate::{sys, EsCodesigningFlags, EsProcess};
+
+        let mut executable = es_file(c"/Applications/Foo.app/Contents/MacOS/Foo");
+        let mut process: sys::es_process_t = unsafe { std::mem::zeroed() };
+        process.executable = &mut executable;
+        process.audit_token.val[5] = 42;
+        process.codesigning_flags = 0x2000_0001 | 0x0001_0000;
+        process.cdhash[0] = 0xab;
+        process.cdhash[19] = 0x01;
+        process.signing_id = es_file(c"com.foo.app").path;
+        process.team_id = es_file(c"").path;
+        process.start_time.tv_sec = 10;
+        process.start_time.tv_usec = 5;
+
+        let decoded = EsProcess::new(&process, 2);
+        assert_eq!(decoded.pid, 42);
+        assert_eq!(
+            decoded.codesigning_flags,
+            EsCodesigningFlags::VALID | EsCodesigningFlags::SIGNED | EsCodesigningFlags::RUNTIME
+        );
+        assert_eq!(
+            decoded.cdhash.to_string(),
+            "ab00000000000000000000000000000000000001"
+        );
+        assert_eq!(decoded.signing_id, "com.foo.app");
+        assert_eq!(decoded.team_id, None);
+        assert!(decoded.tty.is_none());
+        assert_eq!(decoded.start_time, None);
+
+        process.team_id = es_file(c"ABCDE12345").path;
+        let decoded = EsProcess::new(&process, 4);
+        assert_eq!(decoded.team_id.as_deref(), Some("ABCDE12345"));
+        assert_eq!(
+            decoded.start_time,
+            Some(std::time::UNIX_EPOCH + std::time::Duration::new(10, 5000))
+        );
+        assert_eq!(decoded.audit_token.pid(), 42);
+        assert!(decoded.parent_audit_token.is_some());
+
+        // out of range times don't panic
+        process.start_time.tv_sec = -1;
+        process.start_time.tv_usec = 0;
+        assert_eq!(
+            EsProcess::new(&process, 4).start_time,
+            Some(std::time::UNIX_EPOCH - std::time::Duration::from_secs(1))
+        );
+        process.start_time.tv_usec = 999_999;
+        for sec in [i64::MAX, i64::MIN] {
+            process.start_time.tv_sec = sec;
+            EsProcess::new(&process, 4);
+        }
+        process.start_time.tv_sec = 10;
+        for usec in [-1, 1_000_000, i32::MAX] {
+            process.start_time.tv_usec = usec;
+            assert_eq!(EsProcess::new(&process, 4).start_time, None);
+        }
+    }
+
+    #[test]
+    pub fn test_process_lifecycle_events_are_decoded() {
+        use crate::{
+            sys, EsFork, EsProcCheck, EsProcCheckType, EsProcSuspendResume,
+            EsProcSuspendResumeType, EsSignal,
+        };
+
+        let mut executable = es_file(c"/usr/bin/make");
+        let mut process: sys::es_process_t = unsafe { std::mem::zeroed() };
+        process.executable = &mut executable;
+        process.audit_token.val[5] = 42;
+
+        let mut fork: sys::es_event_fork_t = unsafe { std::mem::zeroed() };
+        fork.child = &mut process;
+        assert_eq!(EsFork::new(&fork, 6).unwrap().child.pid, 42);
+        fork.child = std::ptr::null_mut();
+        assert!(EsFork::new(&fork, 6).is_none());
+
+        let mut signal: sys::es_event_signal_t = unsafe { std::mem::zeroed() };
+        signal.sig = 9;
+        signal.target = &mut process;
+        let decoded = EsSignal::new(&signal, 6).unwrap();
+        assert_eq!(decoded.signal, 9);
+        assert_eq!(decoded.target.exe.path, "/usr/bin/make");
+
+        let mut check: sys::es_event_proc_check_t = unsafe { std::mem::zeroed() };
+        check.type_ = sys::es_proc_check_type_t_ES_PROC_CHECK_TYPE_LISTPIDS;
+        check.flavor = 1;
+        let decoded = EsProcCheck::new(&check, 6);
+        assert!(decoded.target.is_none());
+        assert_eq!(decoded.ty, EsProcCheckType::ListPids);
+        assert_eq!(decoded.flavor, 1);
+        check.target = &mut process;
+        check.type_ = 100;
+        let decoded = EsProcCheck::new(&check, 6);
+        assert_eq!(decoded.target.unwrap().pid, 42);
+        assert_eq!(decoded.ty, EsProcCheckType::Unknown(100));
+
+        let mut suspend: sys::es_event_proc_suspend_resume_t = unsafe { std::mem::zeroed() };
+        suspend.target = &mut process;
+        suspend.type_ = sys::es_proc_suspend_resume_type_t_ES_PROC_SUSPEND_RESUME_TYPE_RESUME;
+        let decoded = EsProcSuspendResume::new(&suspend, 6);
+        assert_eq!(decoded.target.unwrap().pid, 42);
+        assert_eq!(decoded.ty, EsProcSuspendResumeType::Resume);
+    }
+
+    #[test]
+    pub fn test_metadata_events_are_decoded() {
+        use crate::{
+            sys, EsAcl, EsFileFlags, EsSetAcl, EsSetFlags, EsSetMode, EsSetOwner, EsUtimes,
+            SimulatedBackend,
+        };
+
+        let mut file = es_file(c"/etc/sudoers");
+
+        let mut setmode: sys::es_event_setmode_t = unsafe { std::mem::zeroed() };
+        setmode.mode = 0o4755;
+        assert!(EsSetMode::new(&setmode).is_none());
+        setmode.target = &mut file;
+        let decoded = EsSetMode::new(&setmode).unwrap();
+        assert_eq!(decoded.target.path, "/etc/sudoers");
+        assert_eq!(decoded.mode, 0o4755);
+
+        let mut setflags: sys::es_event_setflags_t = unsafe { std::mem::zeroed() };
+        setflags.target = &mut file;
+        setflags.flags = 0x0002_0000 | 0x0000_8000;
+        assert_eq!(
+            EsSetFlags::new(&setflags).unwrap().flags,
+            EsFileFlags::SF_IMMUTABLE | EsFileFlags::UF_HIDDEN
+        );
+
+        let mut setowner: sys::es_event_setowner_t = unsafe { std::mem::zeroed() };
+        setowner.target = &mut file;
+        setowner.uid = 501;
+        setowner.gid = 20;
+        let decoded = EsSetOwner::new(&setowner).unwrap();
+        assert_eq!((decoded.uid, decoded.gid), (501, 20));
+
+        let backend = SimulatedBackend::new();
+        let mut setacl: sys::es_event_setacl_t = unsafe { std::mem::zeroed() };
+        setacl.target = &mut file;
+        setacl.set_or_clear = sys::es_set_or_clear_t_ES_CLEAR;
+        assert_eq!(EsSetAcl::new(&setacl, &backend).unwrap().acl, EsAcl::Clear);
+        setacl.set_or_clear = sys::es_set_or_clear_t_ES_SET;
+        assert_eq!(
+            EsSetAcl::new(&setacl, &backend).unwrap().acl,
+            EsAcl::Set {
+                entries: 0,
+                text: None
+            }
+        );
+
+        let mut utimes: sys::es_event_utimes_t = unsafe { std::mem::zeroed() };
+        utimes.target = &mut file;
+        utimes.atime.tv_sec = 10;
+        utimes.atime.tv_nsec = 5;
+        utimes.mtime.tv_sec = -1;
+        let decoded = EsUtimes::new(&utimes).unwrap();
+        assert_eq!(
+            decoded.atime,
+            Some(std::time::UNIX_EPOCH + std::time::Duration::new(10, 5))
+        );
+        assert_eq!(
+            decoded.mtime,
+            Some(std::time::UNIX_EPOCH - std::time::Duration::from_secs(1))
+        );
+
+        // UTIME_NOW and UTIME_OMIT, and nanoseconds past a second
+        utimes.atime.tv_sec = i64::MAX;
+        utimes.atime.tv_nsec = -2;
+        utimes.mtime.tv_nsec = -1;
+        let decoded = EsUtimes::new(&utimes).unwrap();
+        assert_eq!((decoded.atime, decoded.mtime), (None, None));
+        utimes.atime.tv_nsec = 1_000_000_000;
+        assert_eq!(EsUtimes::new(&utimes).unwrap().atime, None);
+        utimes.atime.tv_sec = i64::MIN;
+        utimes.atime.tv_nsec = 999_999_999;
+        EsUtimes::new(&utimes).unwrap();
+    }
+
+    #[test]
+    pub fn test_extattr_events_are_decoded() {
+        use crate::{sys, EsAppleExtAttr, EsExtAttr};
+
+        let mut file = es_file(c"/Users/me/Downloads/app.dmg");
+        let mut event: sys::es_event_deleteextattr_t = unsafe { std::mem::zeroed() };
+        event.extattr = sys::es_string_token_t {
+            length: 20,
+            data: c"com.apple.quarantine".as_ptr(),
+        };
+        assert!(EsExtAttr::new(event.target, &event.extattr).is_none());
+
+        event.target = &mut file;
+        let decoded = EsExtAttr::new(event.target, &event.extattr).unwrap();
+        assert_eq!(decoded.target.path, "/Users/me/Downloads/app.dmg");
+        assert_eq!(decoded.name, "com.apple.quarantine");
+        assert_eq!(decoded.apple_attr(), Some(EsAppleExtAttr::Quarantine));
+
+        event.extattr = sys::es_string_token_t {
+            length: 8,
+            data: c"user.tag".as_ptr(),
+        };
+        let decoded = EsExtAttr::new(event.target, &event.extattr).unwrap();
+        assert_eq!(decoded.apple_attr(), None);
+
+        for attr in EsAppleExtAttr::ALL {
+            assert_eq!(EsAppleExtAttr::from_name(attr.name()), Some(attr));
+        }
+    }
+
+    #[test]
+    pub fn test_mount_events_are_decoded() {
+        use crate::{sys, EsMount, EsMountFlags};
+
+        fn copy_str(dst: &mut [std::ffi::c_char], src: &str) {
+            for (dst, src) in dst.iter_mut().zip(src.bytes()) {
+                *dst = src as std::ffi::c_char;
+            }
+        }
+
+        let mut statfs: sys::statfs = unsafe { std::mem::zeroed() };
+        copy_str(&mut statfs.f_fstypename, "msdos");
+        copy_str(&mut statfs.f_mntonname, "/Volumes/USB");
+        copy_str(&mut statfs.f_mntfromname, "/dev/disk4s1");
+        statfs.f_flags = 0x200 | 0x8 | 0x4;
+        statfs.f_owner = 501;
+
+        let mut event: sys::es_event_mount_t = unsafe { std::mem::zeroed() };
+        assert!(EsMount::new(event.statfs).is_none());
+        event.statfs = &mut statfs;
+        let decoded = EsMount::new(event.statfs).unwrap();
+        assert_eq!(decoded.fs_type, "msdos");
+        assert_eq!(decoded.mount_on, "/Volumes/USB");
+        assert_eq!(decoded.mount_from, "/dev/disk4s1");
+        assert_eq!(
+            decoded.flags,
+            EsMountFlags::REMOVABLE | EsMountFlags::NOSUID | EsMountFlags::NOEXEC
+        );
+        assert_eq!(decoded.owner, 501);
+    }
+
+    #[test]
+    pub fn test_task_port_events_are_decoded() {
+        use crate::{sys, EsGetTask, EsGetTaskType, EsRemoteThreadCreate, EsTrace};
+
+        let mut executable = es_file(c"/Applications/Safari.app/Contents/MacOS/Safari");
+        let mut process: sys::es_process_t = unsafe { std::mem::zeroed() };
+        process.executable = &mut executable;
+        process.audit_token.val[5] = 42;
+
+        let ty = sys::es_get_task_type_t_ES_GET_TASK_TYPE_IDENTITY_TOKEN;
+        let task = EsGetTask::new(&process, ty, 6).unwrap();
+        assert_eq!(task.target.pid, 42);
+        assert_eq!(task.ty, Some(EsGetTaskType::IdentityToken));
+        assert_eq!(EsGetTask::new(&process, ty, 4).unwrap().ty, None);
+        assert!(EsGetTask::new(std::ptr::null(), ty, 6).is_none());
+
+        let mut trace: sys::es_event_trace_t = unsafe { std::mem::zeroed() };
+        trace.target = &mut process;
+        assert_eq!(EsTrace::new(&trace, 6).unwrap().target.pid, 42);
+
+        let mut registers = [1u8, 2, 3, 4];
+        let mut state = sys::es_thread_state_t {
+            flavor: 6,
+            state: sys::es_token_t {
+                size: registers.len(),
+                data: registers.as_mut_ptr(),
+            },
+        };
+        let mut remote: sys::es_event_remote_thread_create_t = unsafe { std::mem::zeroed() };
+        remote.target = &mut process;
+        let decoded = EsRemoteThreadCreate::new(&remote, 6).unwrap();
+        assert_eq!(decoded.target.pid, 42);
+        assert!(decoded.thread_state.is_none());
+
+        remote.thread_state = &mut state;
+        let thread_state = EsRemoteThreadCreate::new(&remote, 6)
+            .unwrap()
+            .thread_state
+            .unwrap();
+        assert_eq!(thread_state.flavor, 6);
+        assert_eq!(thread_state.state, registers);
+    }
+
+    #[test]
+    pub fn test_exit_status_is_decoded() {
+        use crate::EsExitStatus;
+
+        assert_eq!(EsExitStatus::from_raw(0), EsExitStatus::Exited(0));
+        assert_eq!(EsExitStatus::from_raw(3 << 8), EsExitStatus::Exited(3));
+        assert_eq!(
+            EsExitStatus::from_raw(9),
+            EsExitStatus::Signaled {
+                signal: 9,
+                core_dumped: false
+            }
+        );
+        assert_eq!(
+            EsExitStatus::from_raw(0o200 | 11),
+            EsExitStatus::Signaled {
+                signal: 11,
+                core_dumped: true
+            }
+        );
+        assert_eq!(
+            EsExitStatus::from_raw((17 << 8) | 0o177),
+            EsExitStatus::Stopped(17)
+        );
+        assert_eq!(
+            EsExitStatus::from_raw((0x13 << 8) | 0o177),
+            EsExitStatus::Continued
+        );
+    }
+
+    #[test]
+    pub fn test_open_flags_are_decoded() {
+        use crate::{sys, EsOpen, EsOpenFlags};
+
+        let mut file = es_file(c"/etc/hosts");
+        let mut open: sys::es_event_open_t = unsafe { std::mem::zeroed() };
+        open.fflag = 0x1 | 0x2 | 0x400;
+        open.file = &mut file;
+
+        let decoded = EsOpen::new(&open).unwrap();
+        assert_eq!(decoded.file.path, "/etc/hosts");
+        assert_eq!(
+            decoded.fflag,
+            EsOpenFlags::READ | EsOpenFlags::WRITE | EsOpenFlags::TRUNC
+        );
+
+        open.file = std::ptr::null_mut();
+        assert!(EsOpen::new(&open).is_none());
+    }
+
+    #[test]
+    pub fn test_event_type_raw_round_trip() {
+        use crate::{sys, EsError, EsEventType};
+
+        for raw in 0..sys::es_event_type_t_ES_EVENT_TYPE_LAST {
+            let event = EsEventType::try_from(raw).unwrap();
+            assert_eq!(event.raw(), raw);
+        }
+        assert_eq!(
+            EsEventType::from_raw(sys::es_event_type_t_ES_EVENT_TYPE_NOTIFY_BTM_LAUNCH_ITEM_ADD),
+            EsEventType::NotifyBTMLaunchItemAdd
+        );
+
+        let raw = sys::es_event_type_t_ES_EVENT_TYPE_LAST;
+        assert_eq!(EsEventType::from_raw(raw), EsEventType::Unknown(raw));
+        assert_eq!(EsEventType::try_from(raw), Err(EsError::UnknownEvent(raw)));
+    }
+
+    #[test]
+    pub fn test_unknown_event_is_surfaced() {
+        use std::sync::Arc;
+
+        use crate::{EsActionType, EsClient, EsEventType, SimulatedBackend};
+
+        let sim = Arc::new(SimulatedBackend::new());
+        let mut client = EsClient::with_backend(sim.clone()).unwrap();
+        let event = EsEventType::Unknown(500);
+        client.add_event(event).subscribe().unwrap();
+        assert_eq!(client.subscriptions().unwrap(), [event]);
+
+        sim.emit(EsActionType::Notify, event).unwrap();
+        let msg = client.recv_msg().unwrap();
+        assert_eq!(msg.event, event);
+        assert!(msg.event_data.is_none());
+
+        let action = EsActionType::Unknown(7);
+        sim.emit(action, event).unwrap();
+        let mut msg = client.recv_msg().unwrap();
+        assert_eq!(msg.action, action);
+        assert!(msg.take_auth().is_none());
+    }
 }
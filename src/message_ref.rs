@@ -0,0 +1,310 @@
+//! Borrowed view of a message that decodes fields on demand.
+
+use std::ffi::CStr;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::auth::AuthSettings;
+use crate::backend::EsBackend;
+use crate::{
+    sys, AuditToken, AuthRequest, EsActionType, EsCdHash, EsError, EsEventData, EsEventType,
+    EsMessage, EsMessageCopy, EsProcess,
+};
+
+/// A message as delivered to the ES handler, borrowed instead of copied into an [EsMessage].
+///
+/// Accessors read straight from the underlying `es_message_t` without allocating. The exceptions
+/// are [Self::process] and [Self::event_data], which decode into owned values, and [Self::to_owned]
+/// and [Self::copy], which keep the message beyond the view. The view can't outlive the handler, see
+/// [HandlerMode::CallbackRef](crate::HandlerMode::CallbackRef), or the copy it was taken from,
+/// see [EsMessageCopy::message_ref].
+#[derive(Copy, Clone)]
+pub struct EsMessageRef<'a> {
+    raw: &'a sys::es_message_t,
+    backend: &'a Arc<dyn EsBackend>,
+    origin: Origin<'a>,
+}
+
+/// Where the viewed message comes from, which decides how it can be kept
+#[derive(Copy, Clone)]
+enum Origin<'a> {
+    /// Delivered to the ES handler and retained to keep it, `auth` is only set for messages that
+    /// can be answered
+    Handler { auth: Option<&'a AuthSettings> },
+    /// Made with `es_copy_message`, copies can't be retained and are copied again instead
+    Copy,
+}
+
+impl std::fmt::Debug for EsMessageRef<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EsMessageRef")
+            .field("action", &self.action())
+            .field("event", &self.event())
+            .field("seq_num", &self.seq_num())
+            .field("pid", &self.pid())
+            .field("target_path", &self.target_path())
+            .finish()
+    }
+}
+
+fn c_str<'a>(token: &sys::es_string_token_t) -> Option<&'a CStr> {
+    if token.data.is_null() {
+        None
+    } else {
+        // SAFETY: ES strings are NUL terminated and live as long as their message
+        Some(unsafe { CStr::from_ptr(token.data) })
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn c_path(path: &CStr) -> &Path {
+    use std::os::unix::ffi::OsStrExt;
+    Path::new(std::ffi::OsStr::from_bytes(path.to_bytes()))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn c_path(path: &CStr) -> &Path {
+    Path::new(path.to_str().unwrap_or_default())
+}
+
+/// The file the event operates on, for exec events the executable of the new image
+fn target_file(event: EsEventType, events: &sys::es_events_t) -> *const sys::es_file_t {
+    // SAFETY: only the union member of `event` is read
+    unsafe {
+        match event {
+            EsEventType::AuthExec | EsEventType::NotifyExec => events
+                .exec
+                .target
+                .as_ref()
+                .map_or(std::ptr::null(), |target| target.executable),
+            EsEventType::AuthOpen | EsEventType::NotifyOpen => events.open.file,
+            EsEventType::AuthUnlink | EsEventType::NotifyUnlink => events.unlink.target,
+            EsEventType::AuthRename | EsEventType::NotifyRename => events.rename.source,
+            EsEventType::AuthCreate | EsEventType::NotifyCreate => {
+                match events.create.destination_type {
+                    sys::es_destination_type_t_ES_DESTINATION_TYPE_EXISTING_FILE => {
+                        events.create.destination.existing_file
+                    }
+                    _ => std::ptr::null(),
+                }
+            }
+            EsEventType::AuthLink | EsEventType::NotifyLink => events.link.source,
+            EsEventType::AuthClone | EsEventType::NotifyClone => events.clone.source,
+            EsEventType::AuthCopyFile | EsEventType::NotifyCopyFile => events.copyfile.source,
+            EsEventType::AuthMMap | EsEventType::NotifyMMap => events.mmap.source,
+            EsEventType::NotifyWrite => events.write.target,
+            EsEventType::NotifyClose => events.close.target,
+            EsEventType::AuthReadDir | EsEventType::NotifyReadDir => events.readdir.target,
+            EsEventType::AuthChroot | EsEventType::NotifyChroot => events.chroot.target,
+            EsEventType::AuthTruncate | EsEventType::NotifyTruncate => events.truncate.target,
+            EsEventType::AuthSetMode | EsEventType::NotifySetMode => events.setmode.target,
+            EsEventType::AuthSetFlags | EsEventType::NotifySetFlags => events.setflags.target,
+            EsEventType::AuthSetOwner | EsEventType::NotifySetOwner => events.setowner.target,
+            EsEventType::AuthSetACL | EsEventType::NotifySetACL => events.setacl.target,
+            EsEventType::AuthUtimes | EsEventType::NotifyUtimes => events.utimes.target,
+            EsEventType::AuthSetExtAttr | EsEventType::NotifySetExtAttr => events.setextattr.target,
+            EsEventType::AuthGetExtAttr | EsEventType::NotifyGetExtAttr => events.getextattr.target,
+            EsEventType::AuthDeleteExtAttr | EsEventType::NotifyDeleteExtAttr => {
+                events.deleteextattr.target
+            }
+            EsEventType::AuthListenExtAttr | EsEventType::NotifyListenExtAttr => {
+                events.listextattr.target
+            }
+            _ => std::ptr::null(),
+        }
+    }
+}
+
+impl<'a> EsMessageRef<'a> {
+    pub(crate) fn new(
+        raw: &'a sys::es_message_t,
+        backend: &'a Arc<dyn EsBackend>,
+        auth: Option<&'a AuthSettings>,
+    ) -> Self {
+        Self {
+            raw,
+            backend,
+            origin: Origin::Handler { auth },
+        }
+    }
+
+    /// View of a message copy, see [EsMessageCopy::message_ref]
+    pub(crate) fn from_copy(raw: &'a sys::es_message_t, backend: &'a Arc<dyn EsBackend>) -> Self {
+        Self {
+            raw,
+            backend,
+            origin: Origin::Copy,
+        }
+    }
+
+    fn auth(&self) -> Option<&'a AuthSettings> {
+        match self.origin {
+            Origin::Handler { auth } => auth,
+            Origin::Copy => None,
+        }
+    }
+
+    pub fn action(&self) -> EsActionType {
+        EsActionType::from_raw(self.raw.action_type)
+    }
+
+    pub fn event(&self) -> EsEventType {
+        EsEventType::from_raw(self.raw.event_type)
+    }
+
+    pub fn version(&self) -> u32 {
+        self.raw.version
+    }
+
+    pub fn seq_num(&self) -> u64 {
+        self.raw.seq_num
+    }
+
+    pub fn thread_id(&self) -> Option<u64> {
+        unsafe { self.raw.thread.as_ref() }.map(|thread| thread.thread_id)
+    }
+
+    fn raw_process(&self) -> Option<&'a sys::es_process_t> {
+        unsafe { self.raw.process.as_ref() }
+    }
+
+    /// pid of the process that caused the event
+    pub fn pid(&self) -> Option<i32> {
+        self.audit_token().map(|token| token.pid())
+    }
+
+    pub fn audit_token(&self) -> Option<AuditToken> {
+        self.raw_process()
+            .map(|process| AuditToken::from(process.audit_token))
+    }
+
+    pub fn cdhash(&self) -> Option<EsCdHash> {
+        self.raw_process().map(|process| EsCdHash(process.cdhash))
+    }
+
+    /// Executable of the process that caused the event
+    pub fn exe_path(&self) -> Option<&'a Path> {
+        self.exe_path_cstr().map(c_path)
+    }
+
+    pub fn exe_path_cstr(&self) -> Option<&'a CStr> {
+        let executable = unsafe { self.raw_process()?.executable.as_ref() }?;
+        c_str(&executable.path)
+    }
+
+    /// Signing identifier, empty for unsigned binaries
+    pub fn signing_id(&self) -> Option<&'a CStr> {
+        c_str(&self.raw_process()?.signing_id)
+    }
+
+    /// Team identifier, empty for unsigned and platform binaries
+    pub fn team_id(&self) -> Option<&'a CStr> {
+        c_str(&self.raw_process()?.team_id)
+    }
+
+    /// Path of the file the event operates on, like the opened, unlinked or renamed file. For
+    /// exec events the executable of the new image. `None` for events without a target file.
+    pub fn target_path(&self) -> Option<&'a Path> {
+        self.target_path_cstr().map(c_path)
+    }
+
+    pub fn target_path_cstr(&self) -> Option<&'a CStr> {
+        let target = unsafe { target_file(self.event(), &self.raw.event).as_ref() }?;
+        c_str(&target.path)
+    }
+
+    /// Decode the process that caused the event
+    pub fn process(&self) -> Option<EsProcess> {
+        self.raw_process()
+            .map(|process| EsProcess::new(process, self.raw.version))
+    }
+
+    /// Decode the event payload
+    pub fn event_data(&self) -> Option<EsEventData> {
+        EsEventData::from_message(self.raw, self.event(), self.backend.as_ref())
+    }
+
+    /// Decode the message into an [EsMessage] that stays valid after the handler returns.
+    ///
+    /// The result carries no [AuthRequest], auth events are answered with the decision of the
+    /// callback the view was passed to. Views of an [EsMessageCopy] are copied again, which fails
+    /// with [EsError::Failed] if the copy can't be made.
+    pub fn to_owned(self) -> Result<EsMessage, EsError> {
+        match self.origin {
+            Origin::Handler { .. } => Ok(EsMessage::new(self.raw, self.backend.clone(), None)),
+            Origin::Copy => self.copy().map(EsMessage::from_copy),
+        }
+    }
+
+    /// Decode a message delivered to the handler, taking over the response to auth events
+    pub(crate) fn into_message(self) -> EsMessage {
+        debug_assert!(matches!(self.origin, Origin::Handler { .. }));
+        EsMessage::new(self.raw, self.backend.clone(), self.auth())
+    }
+
+    /// Copy the message into an [EsMessageCopy] that outlives the handler
+    pub fn copy(&self) -> Result<EsMessageCopy, EsError> {
+        EsMessageCopy::new(self.raw, self.backend.clone())
+    }
+
+    /// Retain the message for a response, `None` unless it is an auth event that can be answered
+    pub(crate) fn auth_request(&self) -> Option<AuthRequest> {
+        let auth = self
+            .auth()
+            .filter(|_| self.action() == EsActionType::Auth)?;
+        Some(auth.request(self.raw, self.backend.clone(), self.event()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::{AuthDecision, EsClientBuilder, SimResult, SimulatedBackend};
+
+    #[test]
+    fn test_view_matches_owned() {
+        let sim = Arc::new(SimulatedBackend::new());
+        let seen = Arc::new(Mutex::new(vec![]));
+        let callback_seen = seen.clone();
+        let _client = EsClientBuilder::with_backend(sim.clone())
+            .event(EsEventType::AuthUnlink)
+            .callback_ref(move |msg| {
+                let owned = msg.to_owned().unwrap();
+                let process = owned.process.as_ref().unwrap();
+                assert_eq!(msg.action(), owned.action);
+                assert_eq!(msg.event(), owned.event);
+                assert_eq!(msg.seq_num(), owned.seq_num);
+                assert_eq!(msg.pid(), Some(process.pid));
+                assert_eq!(msg.audit_token(), Some(process.audit_token));
+                assert_eq!(msg.exe_path().unwrap().to_string_lossy(), process.exe.path);
+                match owned.event_data.as_ref().unwrap() {
+                    EsEventData::AuthUnlink(unlink) => {
+                        assert_eq!(
+                            msg.target_path().unwrap().to_string_lossy(),
+                            unlink.target.path
+                        )
+                    }
+                    other => panic!("unexpected payload {other:?}"),
+                }
+
+                callback_seen.lock().unwrap().push(msg.seq_num());
+                Some(AuthDecision::Deny)
+            })
+            .build()
+            .unwrap();
+
+        let target = Path::new("/tmp/victim");
+        sim.emit_file(EsActionType::Auth, EsEventType::AuthUnlink, target);
+        sim.emit_file(EsActionType::Auth, EsEventType::AuthUnlink, target);
+        assert_eq!(*seen.lock().unwrap(), [0, 1]);
+        // the owned copies didn't answer, the callback did
+        let responses = sim.responses();
+        assert_eq!(responses.len(), 2);
+        assert!(responses
+            .iter()
+            .all(|response| response.result == SimResult::Deny));
+        assert_eq!(sim.retained_messages(), 0);
+    }
+}
@@ -0,0 +1,423 @@
+//! Declarative path muting.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+#[cfg(feature = "config")]
+use serde::{Deserialize, Serialize};
+
+use crate::{sys, EsClient, EsError, EsEventType, EsMuteInversion, EsMutePath, EsMutedPath};
+
+/// A muted path, muting all events if `events` is empty
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "config", derive(Deserialize, Serialize))]
+pub struct MuteRule {
+    pub path: PathBuf,
+    pub ty: EsMutePath,
+    #[cfg_attr(feature = "config", serde(default))]
+    pub events: Vec<EsEventType>,
+}
+
+/// The complete set of path mutes and inversions a client should have.
+///
+/// [Self::apply] reconciles a client with the set, only muting and unmuting what differs from
+/// what the client reports through [EsClient::muted_paths_events]. Paths the set has no rule for
+/// are left alone, like the paths macOS mutes for every new client, unless `prune_unmanaged` is
+/// set. With the `config` feature the set can be loaded from TOML or JSON:
+///
+/// ```toml
+/// inverted = ["TargetPath"]
+///
+/// [[rules]]
+/// path = "/Users/foo/Documents"
+/// ty = "TargetPrefix"
+///
+/// [[rules]]
+/// path = "/usr/bin/tar"
+/// ty = "Literal"
+/// events = ["NotifyOpen", "NotifyClose"]
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "config", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "config", serde(default))]
+pub struct MuteSet {
+    pub rules: Vec<MuteRule>,
+    /// kinds of muting that are inverted, all others are not
+    pub inverted: Vec<EsMuteInversion>,
+    /// unmute paths the set has no rule for, including the default mutes of the system
+    pub prune_unmanaged: bool,
+}
+
+/// A single step of reconciling a client with a [MuteSet]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MuteChange {
+    /// Mute `events` of a path, all of them if `None`
+    Mute {
+        path: PathBuf,
+        ty: EsMutePath,
+        events: Option<Vec<EsEventType>>,
+    },
+    /// Unmute `events` of a path, all of them if `None`
+    Unmute {
+        path: PathBuf,
+        ty: EsMutePath,
+        events: Option<Vec<EsEventType>>,
+    },
+    /// Toggle the inversion of a kind of muting
+    Invert(EsMuteInversion),
+}
+
+/// Error of [MuteSet::apply], with the changes that were made before it failed
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MuteApplyError {
+    pub applied: Vec<MuteChange>,
+    pub error: EsError,
+}
+
+impl std::fmt::Display for MuteApplyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} after {} mute changes",
+            self.error,
+            self.applied.len()
+        )
+    }
+}
+
+impl std::error::Error for MuteApplyError {}
+
+impl From<MuteApplyError> for EsError {
+    fn from(err: MuteApplyError) -> Self {
+        err.error
+    }
+}
+
+const INVERSIONS: [EsMuteInversion; 3] = [
+    EsMuteInversion::Process,
+    EsMuteInversion::Path,
+    EsMuteInversion::TargetPath,
+];
+
+fn known_events() -> impl Iterator<Item = EsEventType> {
+    (0..sys::es_event_type_t_ES_EVENT_TYPE_LAST).map(EsEventType::from_raw)
+}
+
+impl MuteSet {
+    #[cfg(feature = "config")]
+    pub fn from_toml(config: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(config)
+    }
+
+    #[cfg(feature = "config")]
+    pub fn from_json(config: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(config)
+    }
+
+    /// Changes that turn the `muted` paths into the paths of the set, in the order they should be
+    /// applied. Paths without a rule are only unmuted with `prune_unmanaged`. Inversions aren't
+    /// part of the diff, see [Self::apply].
+    pub fn diff(&self, muted: &[EsMutedPath]) -> Vec<MuteChange> {
+        let mut desired: HashMap<(&PathBuf, EsMutePath), Option<Vec<EsEventType>>> = HashMap::new();
+        for rule in &self.rules {
+            let entry = desired
+                .entry((&rule.path, rule.ty))
+                .or_insert_with(|| Some(vec![]));
+            match entry {
+                Some(events) if !rule.events.is_empty() => {
+                    for event in &rule.events {
+                        if !events.contains(event) {
+                            events.push(*event);
+                        }
+                    }
+                }
+                // an empty rule mutes everything
+                _ => *entry = None,
+            }
+        }
+
+        let mut changes = vec![];
+        for current in muted {
+            let Some(wanted) = desired.remove(&(&current.path, current.ty)) else {
+                if self.prune_unmanaged {
+                    changes.push(MuteChange::Unmute {
+                        path: current.path.clone(),
+                        ty: current.ty,
+                        events: None,
+                    });
+                }
+                continue;
+            };
+
+            match wanted {
+                // events newer than the crate may be reported as well, they are kept
+                None => {
+                    let missing: Vec<_> = known_events()
+                        .filter(|event| !current.events.contains(event))
+                        .collect();
+                    if !missing.is_empty() {
+                        changes.push(MuteChange::Mute {
+                            path: current.path.clone(),
+                            ty: current.ty,
+                            events: None,
+                        });
+                    }
+                }
+                Some(events) => {
+                    let extra: Vec<_> = current
+                        .events
+                        .iter()
+                        .filter(|event| !events.contains(event))
+                        .copied()
+                        .collect();
+                    let missing: Vec<_> = events
+                        .iter()
+                        .filter(|event| !current.events.contains(event))
+                        .copied()
+                        .collect();
+                    if !extra.is_empty() {
+                        changes.push(MuteChange::Unmute {
+                            path: current.path.clone(),
+                            ty: current.ty,
+                            events: Some(extra),
+                        });
+                    }
+                    if !missing.is_empty() {
+                        changes.push(MuteChange::Mute {
+                            path: current.path.clone(),
+                            ty: current.ty,
+                            events: Some(missing),
+                        });
+                    }
+                }
+            }
+        }
+
+        // keep the order of the rules for paths that aren't muted yet
+        for rule in &self.rules {
+            if let Some(events) = desired.remove(&(&rule.path, rule.ty)) {
+                changes.push(MuteChange::Mute {
+                    path: rule.path.clone(),
+                    ty: rule.ty,
+                    events,
+                });
+            }
+        }
+
+        changes
+    }
+
+    /// Bring the path mutes and inversions of `client` in line with the set, returning the
+    /// changes that were made.
+    ///
+    /// Paths are reconciled first and inversions last. Changes are made one by one, if one fails
+    /// the client keeps the ones before it, which are returned with the error.
+    pub fn apply(&self, client: &EsClient) -> Result<Vec<MuteChange>, MuteApplyError> {
+        let failed = |error| MuteApplyError {
+            applied: vec![],
+            error,
+        };
+        let mut changes = self.diff(&client.muted_paths_events().map_err(failed)?);
+        for ty in INVERSIONS {
+            if client.muting_inverted(ty).map_err(failed)? != self.inverted.contains(&ty) {
+                changes.push(MuteChange::Invert(ty));
+            }
+        }
+
+        for (idx, change) in changes.iter().enumerate() {
+            let result = match change {
+                MuteChange::Mute { path, ty, events } => match events {
+                    Some(events) => client.mute_path_events(path, *ty, events),
+                    None => client.mute_path(path, *ty),
+                },
+                MuteChange::Unmute { path, ty, events } => match events {
+                    Some(events) => client.unmute_path_events(path, *ty, events),
+                    None => client.unmute_path(path, *ty),
+                },
+                MuteChange::Invert(ty) => client.invert_muting(*ty),
+            };
+            if let Err(error) = result {
+                changes.truncate(idx);
+                return Err(MuteApplyError {
+                    applied: changes,
+                    error,
+                });
+            }
+        }
+
+        Ok(changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::SimulatedBackend;
+
+    fn rule(path: &str, ty: EsMutePath, events: &[EsEventType]) -> MuteRule {
+        MuteRule {
+            path: path.into(),
+            ty,
+            events: events.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_apply_minimal_diff() {
+        let sim = Arc::new(SimulatedBackend::new());
+        let client = EsClient::with_backend(sim).unwrap();
+        client
+            .mute_path(Path::new("/stale"), EsMutePath::Prefix)
+            .unwrap();
+        client
+            .mute_path_events(
+                Path::new("/usr/bin/tar"),
+                EsMutePath::Literal,
+                &[EsEventType::NotifyOpen, EsEventType::NotifyWrite],
+            )
+            .unwrap();
+        client
+            .mute_path(Path::new("/Users/foo/Documents"), EsMutePath::TargetPrefix)
+            .unwrap();
+
+        let set = MuteSet {
+            rules: vec![
+                rule("/Users/foo/Documents", EsMutePath::TargetPrefix, &[]),
+                rule(
+                    "/usr/bin/tar",
+                    EsMutePath::Literal,
+                    &[EsEventType::NotifyOpen, EsEventType::NotifyClose],
+                ),
+                rule(
+                    "/opt/backup",
+                    EsMutePath::Prefix,
+                    &[EsEventType::NotifyExec],
+                ),
+            ],
+            inverted: vec![EsMuteInversion::TargetPath],
+            prune_unmanaged: true,
+        };
+
+        let changes = set.apply(&client).unwrap();
+        assert_eq!(
+            changes,
+            [
+                MuteChange::Unmute {
+                    path: "/stale".into(),
+                    ty: EsMutePath::Prefix,
+                    events: None
+                },
+                MuteChange::Unmute {
+                    path: "/usr/bin/tar".into(),
+                    ty: EsMutePath::Literal,
+                    events: Some(vec![EsEventType::NotifyWrite])
+                },
+                MuteChange::Mute {
+                    path: "/usr/bin/tar".into(),
+                    ty: EsMutePath::Literal,
+                    events: Some(vec![EsEventType::NotifyClose])
+                },
+                MuteChange::Mute {
+                    path: "/opt/backup".into(),
+                    ty: EsMutePath::Prefix,
+                    events: Some(vec![EsEventType::NotifyExec])
+                },
+                MuteChange::Invert(EsMuteInversion::TargetPath),
+            ]
+        );
+
+        assert_eq!(
+            client.muting_inverted(EsMuteInversion::TargetPath),
+            Ok(true)
+        );
+        assert!(set.apply(&client).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_unmanaged_paths_are_kept() {
+        let sim = Arc::new(SimulatedBackend::new());
+        let client = EsClient::with_backend(sim).unwrap();
+        let system = Path::new("/System/Library/Frameworks");
+        client.mute_path(system, EsMutePath::Prefix).unwrap();
+
+        let mut set = MuteSet {
+            rules: vec![rule("/opt/backup", EsMutePath::Prefix, &[])],
+            ..Default::default()
+        };
+        set.apply(&client).unwrap();
+        let muted = client.muted_paths_events().unwrap();
+        assert_eq!(muted.len(), 2);
+        assert_eq!(muted[0].path, system);
+
+        set.prune_unmanaged = true;
+        set.apply(&client).unwrap();
+        let muted = client.muted_paths_events().unwrap();
+        assert_eq!(muted.len(), 1);
+        assert_eq!(muted[0].path, Path::new("/opt/backup"));
+    }
+
+    #[test]
+    fn test_failed_apply_reports_applied_changes() {
+        let sim = Arc::new(SimulatedBackend::new());
+        let client = EsClient::with_backend(sim).unwrap();
+
+        let set = MuteSet {
+            rules: vec![
+                rule("/opt/backup", EsMutePath::Prefix, &[]),
+                rule("/tmp/\0", EsMutePath::Literal, &[]),
+            ],
+            inverted: vec![EsMuteInversion::Path],
+            ..Default::default()
+        };
+        let err = set.apply(&client).unwrap_err();
+        assert_eq!(err.error, EsError::InvalidArgument);
+        assert_eq!(
+            err.applied,
+            [MuteChange::Mute {
+                path: "/opt/backup".into(),
+                ty: EsMutePath::Prefix,
+                events: None
+            }]
+        );
+        // inversions come last and weren't applied
+        assert_eq!(client.muting_inverted(EsMuteInversion::Path), Ok(false));
+    }
+
+    #[test]
+    #[cfg(feature = "config")]
+    fn test_load_config() {
+        let toml = r#"
+            inverted = ["TargetPath"]
+
+            [[rules]]
+            path = "/Users/foo/Documents"
+            ty = "TargetPrefix"
+
+            [[rules]]
+            path = "/usr/bin/tar"
+            ty = "Literal"
+            events = ["NotifyOpen"]
+        "#;
+        let set = MuteSet::from_toml(toml).unwrap();
+        assert_eq!(
+            set.rules,
+            [
+                rule("/Users/foo/Documents", EsMutePath::TargetPrefix, &[]),
+                rule(
+                    "/usr/bin/tar",
+                    EsMutePath::Literal,
+                    &[EsEventType::NotifyOpen]
+                ),
+            ]
+        );
+        assert_eq!(set.inverted, [EsMuteInversion::TargetPath]);
+
+        let json = r#"{"rules": [{"path": "/tmp", "ty": "Prefix"}]}"#;
+        let set = MuteSet::from_json(json).unwrap();
+        assert_eq!(set.rules, [rule("/tmp", EsMutePath::Prefix, &[])]);
+        assert!(set.inverted.is_empty());
+    }
+}
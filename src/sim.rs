@@ -0,0 +1,1284 @@
+//! In-process stand-in for the Endpoint Security subsystem.
+
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::path::{Path, PathBuf};
+use std::ptr::NonNull;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::backend::{EsBackend, EsHandler};
+use crate::message_ref::c_path;
+use crate::{sys, AuditToken, EsActionType, EsEventType, EsMuteInversion, EsMutePath};
+
+/// Message version of the headers `sys.rs` was generated from (macOS 13)
+const SIM_MESSAGE_VERSION: u32 = 6;
+
+/// Outcome of an auth response recorded by [SimulatedBackend]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimResult {
+    Allow,
+    Deny,
+    /// authorized flags of a flags response
+    Flags(u32),
+}
+
+/// An auth response the client sent to [SimulatedBackend]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimResponse {
+    /// sequence number of the message that was responded to
+    pub seq_num: u64,
+    pub event: EsEventType,
+    pub result: SimResult,
+    /// whether the client asked for the result to be cached
+    pub cache: bool,
+}
+
+/// Backend that emulates the ES subsystem in-process.
+///
+/// Messages are injected with [Self::emit] and delivered only if the client is subscribed to the
+/// event and hasn't muted the emitting process. Responses and mutes are recorded so tests can
+/// assert on them. Simulated messages carry the envelope (action, event type, sequence number and
+/// the current process) and a zeroed event payload, so events whose payload points to a target
+/// decode to `None`. [Self::emit_file] fills in the target file of file system events.
+///
+/// # Example
+/// ```
+///     use std::sync::Arc;
+///     use endpointsecurity_rs::{EsActionType, EsClient, EsEventType, SimulatedBackend};
+///
+///     let sim = Arc::new(SimulatedBackend::new());
+///     let mut client = EsClient::with_backend(sim.clone()).unwrap();
+///     client.add_event(EsEventType::AuthOpen).subscribe().unwrap();
+///
+///     sim.emit(EsActionType::Auth, EsEventType::AuthOpen).unwrap();
+///     let mut msg = client.recv_msg().unwrap();
+///     msg.take_auth().unwrap().allow().unwrap();
+///     assert_eq!(sim.responses().len(), 1);
+/// ```
+pub struct SimulatedBackend {
+    state: Mutex<SimState>,
+    /// origin of the simulated mach clock
+    epoch: Instant,
+}
+
+impl Default for SimulatedBackend {
+    fn default() -> Self {
+        Self {
+            state: Mutex::default(),
+            epoch: Instant::now(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct SimState {
+    handler: Option<Arc<EsHandler>>,
+    subscriptions: Vec<sys::es_event_type_t>,
+    /// muted processes and their muted events
+    muted_processes: Vec<(AuditToken, Vec<sys::es_event_type_t>)>,
+    /// muted paths and their muted events
+    muted_paths: Vec<(PathBuf, EsMutePath, Vec<sys::es_event_type_t>)>,
+    /// paths muted for every new client
+    default_muted_paths: Vec<(PathBuf, EsMutePath)>,
+    /// whether muting is inverted, by `es_mute_inversion_type_t`
+    inverted: [bool; 3],
+    responses: Vec<SimResponse>,
+    /// live messages keyed by their address
+    messages: HashMap<usize, SimMessage>,
+    /// copies made with `copy_message`, keyed by their address
+    copies: HashMap<usize, SimMessage>,
+    next_seq: u64,
+    /// time given to answer auth messages, ES uses different values depending on the event
+    deadline: Option<Duration>,
+    cache_clears: usize,
+    clear_cache_throttled: bool,
+}
+
+struct SimMessage {
+    message: Box<sys::es_message_t>,
+    process: Box<sys::es_process_t>,
+    _executable: Box<sys::es_file_t>,
+    path: CString,
+    target: Option<PathBuf>,
+    /// files the event payload points to and their paths
+    files: Vec<(Box<sys::es_file_t>, CString)>,
+    action: EsActionType,
+    event: EsEventType,
+    refs: usize,
+    responded: bool,
+}
+
+// SAFETY: the raw pointers inside `message` only point into allocations owned by the same
+// `SimMessage`, which is only ever accessed behind the `SimulatedBackend` mutex.
+unsafe impl Send for SimMessage {}
+
+impl SimMessage {
+    fn new(
+        action: EsActionType,
+        event: EsEventType,
+        seq_num: u64,
+        exe: &Path,
+        target: Option<&Path>,
+        mach_time: u64,
+        deadline: u64,
+    ) -> Self {
+        let (mut executable, path) = sim_file(exe);
+
+        let mut process: Box<sys::es_process_t> = Box::new(unsafe { std::mem::zeroed() });
+        process.audit_token.val[5] = std::process::id();
+        process.executable = &mut *executable;
+
+        let mut message: Box<sys::es_message_t> = Box::new(unsafe { std::mem::zeroed() });
+        message.version = SIM_MESSAGE_VERSION;
+        message.seq_num = seq_num;
+        message.mach_time = mach_time;
+        message.deadline = deadline;
+        message.action_type = match action {
+            EsActionType::Auth => sys::es_action_type_t_ES_ACTION_TYPE_AUTH,
+            EsActionType::Notify => sys::es_action_type_t_ES_ACTION_TYPE_NOTIFY,
+            EsActionType::Unknown(action) => action,
+        };
+        message.event_type = event.raw();
+        message.process = &mut *process;
+
+        let mut files = vec![];
+        if let Some(target) = target {
+            let mut file = |path: &Path| {
+                let (mut file, path) = sim_file(path);
+                let ptr: *mut sys::es_file_t = &mut *file;
+                files.push((file, path));
+                ptr
+            };
+            let parent = target.parent().unwrap_or(target);
+            set_target(&mut message.event, event, file(target), file(parent));
+        }
+
+        Self {
+            message,
+            process,
+            _executable: executable,
+            path,
+            target: target.map(Path::to_path_buf),
+            files,
+            action,
+            event,
+            refs: 1,
+            responded: false,
+        }
+    }
+}
+
+/// A file pointing to its own copy of `path`
+fn sim_file(path: &Path) -> (Box<sys::es_file_t>, CString) {
+    let path = to_cstring(path);
+
+    // SAFETY: the ES structs are plain C data for which all zeroes is a valid value
+    let mut file: Box<sys::es_file_t> = Box::new(unsafe { std::mem::zeroed() });
+    file.path = sys::es_string_token_t {
+        length: path.as_bytes().len(),
+        data: path.as_ptr(),
+    };
+    (file, path)
+}
+
+/// Point the payload of file system events to `target`, and to its `parent` directory where the
+/// event has one. Other payloads stay zeroed.
+fn set_target(
+    events: &mut sys::es_events_t,
+    event: EsEventType,
+    target: *mut sys::es_file_t,
+    parent: *mut sys::es_file_t,
+) {
+    match event {
+        EsEventType::AuthOpen | EsEventType::NotifyOpen => events.open.file = target,
+        EsEventType::AuthUnlink | EsEventType::NotifyUnlink => {
+            events.unlink.target = target;
+            events.unlink.parent_dir = parent;
+        }
+        EsEventType::AuthRename | EsEventType::NotifyRename => events.rename.source = target,
+        EsEventType::AuthClone | EsEventType::NotifyClone => {
+            events.clone.source = target;
+            events.clone.target_dir = parent;
+        }
+        EsEventType::AuthCopyFile | EsEventType::NotifyCopyFile => events.copyfile.source = target,
+        EsEventType::AuthMMap | EsEventType::NotifyMMap => events.mmap.source = target,
+        EsEventType::NotifyWrite => events.write.target = target,
+        EsEventType::NotifyClose => events.close.target = target,
+        EsEventType::AuthReadDir | EsEventType::NotifyReadDir => events.readdir.target = target,
+        EsEventType::AuthChroot | EsEventType::NotifyChroot => events.chroot.target = target,
+        EsEventType::AuthTruncate | EsEventType::NotifyTruncate => events.truncate.target = target,
+        EsEventType::AuthSetMode | EsEventType::NotifySetMode => events.setmode.target = target,
+        EsEventType::AuthSetFlags | EsEventType::NotifySetFlags => events.setflags.target = target,
+        EsEventType::AuthSetOwner | EsEventType::NotifySetOwner => events.setowner.target = target,
+        EsEventType::AuthSetACL | EsEventType::NotifySetACL => events.setacl.target = target,
+        EsEventType::AuthUtimes | EsEventType::NotifyUtimes => events.utimes.target = target,
+        EsEventType::AuthSetExtAttr | EsEventType::NotifySetExtAttr => {
+            events.setextattr.target = target
+        }
+        EsEventType::AuthGetExtAttr | EsEventType::NotifyGetExtAttr => {
+            events.getextattr.target = target
+        }
+        EsEventType::AuthDeleteExtAttr | EsEventType::NotifyDeleteExtAttr => {
+            events.deleteextattr.target = target
+        }
+        EsEventType::AuthListenExtAttr | EsEventType::NotifyListenExtAttr => {
+            events.listextattr.target = target
+        }
+        _ => {}
+    }
+}
+
+impl SimState {
+    /// Target path mutes only apply to messages emitted with a target
+    fn is_muted(
+        &self,
+        audit_token: AuditToken,
+        event: EsEventType,
+        exe: &Path,
+        target: Option<&Path>,
+    ) -> bool {
+        let event = event.raw();
+        let process_muted = self
+            .muted_processes
+            .iter()
+            .any(|(muted, events)| *muted == audit_token && events.contains(&event));
+        let path_muted = |types: [EsMutePath; 2], path: &Path| {
+            self.muted_paths.iter().any(|(muted, ty, events)| {
+                let matches = match ty {
+                    EsMutePath::Prefix | EsMutePath::TargetPrefix => path.starts_with(muted),
+                    EsMutePath::Literal | EsMutePath::TargetLiteral => path == muted,
+                    EsMutePath::Unknown(_) => false,
+                };
+                types.contains(ty) && matches && events.contains(&event)
+            })
+        };
+        let exe_muted = path_muted([EsMutePath::Prefix, EsMutePath::Literal], exe);
+        let target_muted = target.is_some_and(|target| {
+            path_muted(
+                [EsMutePath::TargetPrefix, EsMutePath::TargetLiteral],
+                target,
+            ) != self.inverted[EsMuteInversion::TargetPath as usize]
+        });
+
+        process_muted != self.inverted[EsMuteInversion::Process as usize]
+            || exe_muted != self.inverted[EsMuteInversion::Path as usize]
+            || target_muted
+    }
+
+    fn muted_path(&mut self, path: &Path, ty: EsMutePath) -> &mut Vec<sys::es_event_type_t> {
+        let idx = match self
+            .muted_paths
+            .iter()
+            .position(|(muted, muted_ty, _)| muted == path && *muted_ty == ty)
+        {
+            Some(idx) => idx,
+            None => {
+                self.muted_paths.push((path.to_path_buf(), ty, vec![]));
+                self.muted_paths.len() - 1
+            }
+        };
+        &mut self.muted_paths[idx].2
+    }
+}
+
+impl SimulatedBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn state(&self) -> MutexGuard<'_, SimState> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Current time of the simulated mach clock, in nanoseconds
+    fn mach_now(&self) -> u64 {
+        self.epoch.elapsed().as_nanos() as u64
+    }
+
+    /// Set the time auth messages emitted from now on have to be answered in, 60 seconds by
+    /// default
+    pub fn set_deadline(&self, deadline: Duration) {
+        self.state().deadline = Some(deadline);
+    }
+
+    /// Deliver a message for `event` from the current process to the client.
+    ///
+    /// Returns the sequence number of the message, or `None` if it wasn't delivered because no
+    /// client is connected, the client isn't subscribed to `event` or the process is muted.
+    pub fn emit(&self, action: EsActionType, event: EsEventType) -> Option<u64> {
+        self.emit_message(action, event, None)
+    }
+
+    /// Like [Self::emit], for a file system event on `target`.
+    ///
+    /// The payload points to `target` and, for unlink and clone events, its parent directory.
+    /// It isn't delivered if `target` is muted.
+    pub fn emit_file(
+        &self,
+        action: EsActionType,
+        event: EsEventType,
+        target: &Path,
+    ) -> Option<u64> {
+        self.emit_message(action, event, Some(target))
+    }
+
+    fn emit_message(
+        &self,
+        action: EsActionType,
+        event: EsEventType,
+        target: Option<&Path>,
+    ) -> Option<u64> {
+        let exe = std::env::current_exe().unwrap_or_default();
+
+        let (handler, message, seq_num) = {
+            let mut state = self.state();
+            let handler = state.handler.clone()?;
+            if !state.subscriptions.contains(&(event.raw())) {
+                return None;
+            }
+
+            let seq_num = state.next_seq;
+            let now = self.mach_now();
+            let deadline = state.deadline.unwrap_or(Duration::from_secs(60));
+            let deadline = now + deadline.as_nanos() as u64;
+            let sim = SimMessage::new(action, event, seq_num, &exe, target, now, deadline);
+            if state.is_muted(sim.process.audit_token.into(), event, &exe, target) {
+                return None;
+            }
+            state.next_seq += 1;
+
+            let message: *const sys::es_message_t = &*sim.message;
+            state.messages.insert(message as usize, sim);
+            (handler, message, seq_num)
+        };
+
+        // SAFETY: the reference taken when the message was created keeps it alive until the
+        // release below
+        let message = unsafe { &*message };
+        (*handler)(message);
+        self.release_message(message);
+
+        Some(seq_num)
+    }
+
+    /// Auth responses sent by the client, oldest first
+    pub fn responses(&self) -> Vec<SimResponse> {
+        self.state().responses.clone()
+    }
+
+    /// Events the client is currently subscribed to
+    pub fn subscriptions(&self) -> Vec<EsEventType> {
+        let events = self.state().subscriptions.clone();
+        events.into_iter().map(EsEventType::from_raw).collect()
+    }
+
+    /// Paths muted by the client
+    pub fn muted_paths(&self) -> Vec<(PathBuf, EsMutePath)> {
+        let state = self.state();
+        let muted = state.muted_paths.iter();
+        muted.map(|(path, ty, _)| (path.clone(), *ty)).collect()
+    }
+
+    /// Mute `path` for all events of every client connected from now on, like macOS does for
+    /// some system paths
+    pub fn mute_path_by_default(&self, path: &Path, ty: EsMutePath) {
+        self.state()
+            .default_muted_paths
+            .push((path.to_path_buf(), ty));
+    }
+
+    /// Number of times the client cleared the auth result cache
+    pub fn cache_clears(&self) -> usize {
+        self.state().cache_clears
+    }
+
+    /// Fail clearing the cache as if it was called too often
+    pub fn set_clear_cache_throttled(&self, throttled: bool) {
+        self.state().clear_cache_throttled = throttled;
+    }
+
+    /// Number of messages that were delivered and haven't been released yet
+    pub fn retained_messages(&self) -> usize {
+        self.state().messages.len()
+    }
+
+    /// Number of message copies that haven't been freed yet
+    pub fn copied_messages(&self) -> usize {
+        self.state().copies.len()
+    }
+
+    /// Returns true while a client is connected
+    pub fn is_connected(&self) -> bool {
+        self.state().handler.is_some()
+    }
+
+    fn record_response(
+        &self,
+        message: &sys::es_message_t,
+        result: SimResult,
+        cache: bool,
+    ) -> sys::es_respond_result_t {
+        let mut state = self.state();
+        let Some(sim) = state.messages.get_mut(&(message as *const _ as usize)) else {
+            return sys::es_respond_result_t_ES_RESPOND_RESULT_NOT_FOUND;
+        };
+        if sim.action != EsActionType::Auth {
+            return sys::es_respond_result_t_ES_RESPOND_RESULT_ERR_INVALID_ARGUMENT;
+        }
+        if sim.responded {
+            return sys::es_respond_result_t_ES_RESPOND_RESULT_ERR_DUPLICATE_RESPONSE;
+        }
+        // flag events only take flags responses and the other way around
+        if matches!(result, SimResult::Flags(_)) != sim.event.accepts_flags_response() {
+            return sys::es_respond_result_t_ES_RESPOND_RESULT_ERR_EVENT_TYPE;
+        }
+        sim.responded = true;
+
+        let response = SimResponse {
+            seq_num: message.seq_num,
+            event: sim.event,
+            result,
+            cache,
+        };
+        state.responses.push(response);
+        sys::es_respond_result_t_ES_RESPOND_RESULT_SUCCESS
+    }
+}
+
+fn all_events() -> Vec<sys::es_event_type_t> {
+    (0..sys::es_event_type_t_ES_EVENT_TYPE_LAST).collect()
+}
+
+/// Keeps the bytes of paths that aren't valid UTF-8
+fn to_path(path: &CStr) -> PathBuf {
+    c_path(path).to_path_buf()
+}
+
+fn to_cstring(path: &Path) -> CString {
+    CString::new(path.as_os_str().as_encoded_bytes()).unwrap_or_default()
+}
+
+impl EsBackend for SimulatedBackend {
+    fn new_client(&self, handler: EsHandler) -> sys::es_new_client_result_t {
+        let mut state = self.state();
+        if state.handler.is_some() {
+            return sys::es_new_client_result_t_ES_NEW_CLIENT_RESULT_ERR_TOO_MANY_CLIENTS;
+        }
+        state.handler = Some(Arc::new(handler));
+        state.muted_paths = state
+            .default_muted_paths
+            .iter()
+            .map(|(path, ty)| (path.clone(), *ty, all_events()))
+            .collect();
+        sys::es_new_client_result_t_ES_NEW_CLIENT_RESULT_SUCCESS
+    }
+
+    fn delete_client(&self) -> sys::es_return_t {
+        let mut state = self.state();
+        if state.handler.take().is_none() {
+            return sys::es_return_t_ES_RETURN_ERROR;
+        }
+        state.subscriptions.clear();
+        state.muted_processes.clear();
+        state.muted_paths.clear();
+        state.inverted = Default::default();
+        sys::es_return_t_ES_RETURN_SUCCESS
+    }
+
+    fn subscribe(&self, events: &[sys::es_event_type_t]) -> sys::es_return_t {
+        let mut state = self.state();
+        for event in events {
+            if !state.subscriptions.contains(event) {
+                state.subscriptions.push(*event);
+            }
+        }
+        sys::es_return_t_ES_RETURN_SUCCESS
+    }
+
+    fn unsubscribe(&self, events: &[sys::es_event_type_t]) -> sys::es_return_t {
+        self.state()
+            .subscriptions
+            .retain(|event| !events.contains(event));
+        sys::es_return_t_ES_RETURN_SUCCESS
+    }
+
+    fn unsubscribe_all(&self) -> sys::es_return_t {
+        self.state().subscriptions.clear();
+        sys::es_return_t_ES_RETURN_SUCCESS
+    }
+
+    fn subscriptions(&self) -> Option<Vec<sys::es_event_type_t>> {
+        Some(self.state().subscriptions.clone())
+    }
+
+    fn respond_auth_result(
+        &self,
+        message: &sys::es_message_t,
+        result: sys::es_auth_result_t,
+        cache: bool,
+    ) -> sys::es_respond_result_t {
+        let result = match result {
+            sys::es_auth_result_t_ES_AUTH_RESULT_ALLOW => SimResult::Allow,
+            sys::es_auth_result_t_ES_AUTH_RESULT_DENY => SimResult::Deny,
+            _ => return sys::es_respond_result_t_ES_RESPOND_RESULT_ERR_INVALID_ARGUMENT,
+        };
+        self.record_response(message, result, cache)
+    }
+
+    fn respond_flags_result(
+        &self,
+        message: &sys::es_message_t,
+        flags: u32,
+        cache: bool,
+    ) -> sys::es_respond_result_t {
+        self.record_response(message, SimResult::Flags(flags), cache)
+    }
+
+    fn mach_time_to_instant(&self, mach_time: u64) -> Instant {
+        self.epoch + Duration::from_nanos(mach_time)
+    }
+
+    fn clear_cache(&self) -> sys::es_clear_cache_result_t {
+        let mut state = self.state();
+        if state.clear_cache_throttled {
+            return sys::es_clear_cache_result_t_ES_CLEAR_CACHE_RESULT_ERR_THROTTLE;
+        }
+        state.cache_clears += 1;
+        sys::es_clear_cache_result_t_ES_CLEAR_CACHE_RESULT_SUCCESS
+    }
+
+    fn mute_process(&self, audit_token: &sys::audit_token_t) -> sys::es_return_t {
+        self.mute_process_events(audit_token, &all_events())
+    }
+
+    fn mute_process_events(
+        &self,
+        audit_token: &sys::audit_token_t,
+        events: &[sys::es_event_type_t],
+    ) -> sys::es_return_t {
+        let audit_token = AuditToken::from(*audit_token);
+        let mut state = self.state();
+        let muted = match state
+            .muted_processes
+            .iter_mut()
+            .position(|(muted, _)| *muted == audit_token)
+        {
+            Some(idx) => &mut state.muted_processes[idx].1,
+            None => {
+                state.muted_processes.push((audit_token, vec![]));
+                &mut state.muted_processes.last_mut().unwrap().1
+            }
+        };
+        for event in events {
+            if !muted.contains(event) {
+                muted.push(*event);
+            }
+        }
+        sys::es_return_t_ES_RETURN_SUCCESS
+    }
+
+    fn unmute_process(&self, audit_token: &sys::audit_token_t) -> sys::es_return_t {
+        let audit_token = AuditToken::from(*audit_token);
+        self.state()
+            .muted_processes
+            .retain(|(muted, _)| *muted != audit_token);
+        sys::es_return_t_ES_RETURN_SUCCESS
+    }
+
+    fn unmute_process_events(
+        &self,
+        audit_token: &sys::audit_token_t,
+        events: &[sys::es_event_type_t],
+    ) -> sys::es_return_t {
+        let audit_token = AuditToken::from(*audit_token);
+        let mut state = self.state();
+        for (muted, muted_events) in state.muted_processes.iter_mut() {
+            if *muted == audit_token {
+                muted_events.retain(|event| !events.contains(event));
+            }
+        }
+        state
+            .muted_processes
+            .retain(|(_, muted_events)| !muted_events.is_empty());
+        sys::es_return_t_ES_RETURN_SUCCESS
+    }
+
+    fn muted_processes_events(
+        &self,
+    ) -> Option<Vec<(sys::audit_token_t, Vec<sys::es_event_type_t>)>> {
+        let state = self.state();
+        let muted = state
+            .muted_processes
+            .iter()
+            .map(|(audit_token, events)| ((*audit_token).into(), events.clone()))
+            .collect();
+        Some(muted)
+    }
+
+    fn mute_path(&self, path: &CStr, ty: sys::es_mute_path_type_t) -> sys::es_return_t {
+        self.mute_path_events(path, ty, &all_events())
+    }
+
+    fn mute_path_events(
+        &self,
+        path: &CStr,
+        ty: sys::es_mute_path_type_t,
+        events: &[sys::es_event_type_t],
+    ) -> sys::es_return_t {
+        let ty = EsMutePath::from_raw(ty);
+        let mut state = self.state();
+        let muted = state.muted_path(&to_path(path), ty);
+        for event in events {
+            if !muted.contains(event) {
+                muted.push(*event);
+            }
+        }
+        sys::es_return_t_ES_RETURN_SUCCESS
+    }
+
+    fn unmute_path(&self, path: &CStr, ty: sys::es_mute_path_type_t) -> sys::es_return_t {
+        self.unmute_path_events(path, ty, &all_events())
+    }
+
+    fn unmute_path_events(
+        &self,
+        path: &CStr,
+        ty: sys::es_mute_path_type_t,
+        events: &[sys::es_event_type_t],
+    ) -> sys::es_return_t {
+        let ty = EsMutePath::from_raw(ty);
+        let path = to_path(path);
+        let mut state = self.state();
+        for (muted, muted_ty, muted_events) in state.muted_paths.iter_mut() {
+            if *muted == path && *muted_ty == ty {
+                muted_events.retain(|event| !events.contains(event));
+            }
+        }
+        state
+            .muted_paths
+            .retain(|(_, _, events)| !events.is_empty());
+        sys::es_return_t_ES_RETURN_SUCCESS
+    }
+
+    fn unmute_all_paths(&self) -> sys::es_return_t {
+        self.state().muted_paths.retain(|(_, ty, _)| ty.is_target());
+        sys::es_return_t_ES_RETURN_SUCCESS
+    }
+
+    fn unmute_all_target_paths(&self) -> sys::es_return_t {
+        self.state()
+            .muted_paths
+            .retain(|(_, ty, _)| !ty.is_target());
+        sys::es_return_t_ES_RETURN_SUCCESS
+    }
+
+    fn muted_paths_events(
+        &self,
+    ) -> Option<Vec<(CString, sys::es_mute_path_type_t, Vec<sys::es_event_type_t>)>> {
+        let state = self.state();
+        let muted = state
+            .muted_paths
+            .iter()
+            .map(|(path, ty, events)| (to_cstring(path), ty.raw(), events.clone()));
+        Some(muted.collect())
+    }
+
+    fn invert_muting(&self, ty: sys::es_mute_inversion_type_t) -> sys::es_return_t {
+        match self.state().inverted.get_mut(ty as usize) {
+            Some(inverted) => {
+                *inverted = !*inverted;
+                sys::es_return_t_ES_RETURN_SUCCESS
+            }
+            None => sys::es_return_t_ES_RETURN_ERROR,
+        }
+    }
+
+    fn muting_inverted(&self, ty: sys::es_mute_inversion_type_t) -> sys::es_mute_inverted_return_t {
+        match self.state().inverted.get(ty as usize) {
+            Some(true) => sys::es_mute_inverted_return_t_ES_MUTE_INVERTED,
+            Some(false) => sys::es_mute_inverted_return_t_ES_MUTE_NOT_INVERTED,
+            None => sys::es_mute_inverted_return_t_ES_MUTE_INVERTED_ERROR,
+        }
+    }
+
+    fn exec_args(&self, _event: &sys::es_event_exec_t) -> Vec<sys::es_string_token_t> {
+        vec![]
+    }
+
+    fn exec_envs(&self, _event: &sys::es_event_exec_t) -> Vec<sys::es_string_token_t> {
+        vec![]
+    }
+
+    fn exec_fds<'a>(&self, _event: &'a sys::es_event_exec_t) -> Vec<&'a sys::es_fd_t> {
+        vec![]
+    }
+
+    fn acl_entry_count(&self, _event: &sys::es_event_setacl_t) -> usize {
+        0
+    }
+
+    fn acl_text(&self, _event: &sys::es_event_setacl_t) -> Option<String> {
+        None
+    }
+
+    fn retain_message(&self, message: &sys::es_message_t) {
+        let key = message as *const _ as usize;
+        match self.state().messages.get_mut(&key) {
+            Some(sim) => sim.refs += 1,
+            // e.g. a copy, which the system doesn't allow to retain on older releases
+            None => panic!("retained a message that wasn't delivered or is already released"),
+        }
+    }
+
+    fn release_message(&self, message: &sys::es_message_t) {
+        let key = message as *const _ as usize;
+        let mut state = self.state();
+        let Some(sim) = state.messages.get_mut(&key) else {
+            panic!("released a message that wasn't delivered or is already released");
+        };
+        sim.refs -= 1;
+        if sim.refs == 0 {
+            state.messages.remove(&key);
+        }
+    }
+
+    fn copy_message(&self, message: &sys::es_message_t) -> Option<NonNull<sys::es_message_t>> {
+        let mut state = self.state();
+        let key = message as *const _ as usize;
+        let original = state.messages.get(&key).or(state.copies.get(&key))?;
+        let exe = to_path(&original.path);
+        let mut copy = SimMessage::new(
+            original.action,
+            original.event,
+            message.seq_num,
+            &exe,
+            original.target.clone().as_deref(),
+            message.mach_time,
+            message.deadline,
+        );
+
+        let ptr = NonNull::from(&mut *copy.message);
+        state.copies.insert(ptr.as_ptr() as usize, copy);
+        Some(ptr)
+    }
+
+    fn free_message(&self, message: &sys::es_message_t) {
+        let freed = self.state().copies.remove(&(message as *const _ as usize));
+        assert!(freed.is_some(), "freed a message that isn't a live copy");
+    }
+
+    fn message_size(&self, message: &sys::es_message_t) -> usize {
+        let state = self.state();
+        let key = message as *const _ as usize;
+        let Some(sim) = state.copies.get(&key).or_else(|| state.messages.get(&key)) else {
+            return 0;
+        };
+        let files = std::iter::once(&sim.path).chain(sim.files.iter().map(|(_, path)| path));
+        std::mem::size_of::<sys::es_message_t>()
+            + std::mem::size_of::<sys::es_process_t>()
+            + files
+                .map(|path| std::mem::size_of::<sys::es_file_t>() + path.as_bytes().len() + 1)
+                .sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{AuthDecision, EsClient, EsError, EsEventData, EsOpenFlags};
+
+    fn client() -> (Arc<SimulatedBackend>, EsClient) {
+        let sim = Arc::new(SimulatedBackend::new());
+        let client = EsClient::with_backend(sim.clone()).unwrap();
+        (sim, client)
+    }
+
+    #[test]
+    fn test_only_subscribed_events_are_delivered() {
+        let (sim, mut client) = client();
+        client
+            .add_event(EsEventType::NotifyOpen)
+            .subscribe()
+            .unwrap();
+
+        assert!(sim
+            .emit(EsActionType::Notify, EsEventType::NotifyExec)
+            .is_none());
+        let seq_num = sim.emit(EsActionType::Notify, EsEventType::NotifyOpen);
+        assert!(seq_num.is_some());
+
+        let msg = client.try_recv_msg().unwrap().unwrap();
+        assert_eq!(msg.event, EsEventType::NotifyOpen);
+        assert_eq!(Some(msg.seq_num), seq_num);
+        assert_eq!(msg.process.as_ref().unwrap().pid, std::process::id() as i32);
+        assert!(client.try_recv_msg().unwrap().is_none());
+
+        client.unsubscribe(EsEventType::NotifyOpen).unwrap();
+        assert_eq!(
+            client.unsubscribe(EsEventType::NotifyOpen),
+            Err(EsError::NotSubscribed(EsEventType::NotifyOpen))
+        );
+        assert!(sim
+            .emit(EsActionType::Notify, EsEventType::NotifyOpen)
+            .is_none());
+    }
+
+    #[test]
+    fn test_auth_responses_are_recorded() {
+        let (sim, mut client) = client();
+        client
+            .add_event(EsEventType::AuthUnlink)
+            .subscribe()
+            .unwrap();
+
+        sim.emit(EsActionType::Auth, EsEventType::AuthUnlink);
+        sim.emit(EsActionType::Auth, EsEventType::AuthUnlink);
+        let mut msg = client.recv_msg().unwrap();
+        msg.take_auth().unwrap().allow().unwrap();
+        assert!(msg.take_auth().is_none());
+        let mut msg = client.recv_msg().unwrap();
+        msg.take_auth().unwrap().deny().unwrap();
+
+        let results: Vec<_> = sim.responses().iter().map(|resp| resp.result).collect();
+        assert_eq!(results, [SimResult::Allow, SimResult::Deny]);
+        assert!(sim.responses().iter().all(|resp| resp.cache));
+    }
+
+    #[test]
+    fn test_unanswered_auth_gets_default_response() {
+        let (sim, mut client) = client();
+        client
+            .add_event(EsEventType::AuthUnlink)
+            .subscribe()
+            .unwrap();
+
+        sim.emit(EsActionType::Auth, EsEventType::AuthUnlink);
+        drop(client.recv_msg().unwrap());
+
+        client.set_auth_default(AuthDecision::Deny);
+        sim.emit(EsActionType::Auth, EsEventType::AuthUnlink);
+        let mut msg = client.recv_msg().unwrap();
+        let auth = msg.take_auth().unwrap();
+        drop(msg);
+        assert_eq!(sim.responses().len(), 1);
+        assert_eq!(auth.default_decision(), AuthDecision::Deny);
+        drop(auth);
+
+        let results: Vec<_> = sim.responses().iter().map(|resp| resp.result).collect();
+        assert_eq!(results, [SimResult::Allow, SimResult::Deny]);
+        assert!(sim.responses().iter().all(|resp| !resp.cache));
+        assert_eq!(sim.retained_messages(), 0);
+    }
+
+    #[test]
+    fn test_flags_response() {
+        let (sim, mut client) = client();
+        client
+            .add_event(EsEventType::AuthOpen)
+            .add_event(EsEventType::AuthUnlink)
+            .subscribe()
+            .unwrap();
+
+        sim.emit(EsActionType::Auth, EsEventType::AuthOpen);
+        let auth = client.recv_msg().unwrap().take_auth().unwrap();
+        auth.respond_flags(EsOpenFlags::READ).unwrap();
+        sim.emit(EsActionType::Auth, EsEventType::AuthOpen);
+        drop(client.recv_msg().unwrap());
+
+        sim.emit(EsActionType::Auth, EsEventType::AuthUnlink);
+        let auth = client.recv_msg().unwrap().take_auth().unwrap();
+        assert_eq!(
+            auth.respond_flags(EsOpenFlags::READ),
+            Err(EsError::EventType)
+        );
+
+        let results: Vec<_> = sim.responses().iter().map(|resp| resp.result).collect();
+        assert_eq!(
+            results,
+            [
+                SimResult::Flags(1),
+                SimResult::Flags(u32::MAX),
+                SimResult::Allow
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cache_is_selectable() {
+        let (sim, mut client) = client();
+        client
+            .add_event(EsEventType::AuthUnlink)
+            .subscribe()
+            .unwrap();
+
+        sim.emit(EsActionType::Auth, EsEventType::AuthUnlink);
+        let auth = client.recv_msg().unwrap().take_auth().unwrap();
+        auth.cache(false).deny().unwrap();
+        sim.emit(EsActionType::Auth, EsEventType::AuthUnlink);
+        let auth = client.recv_msg().unwrap().take_auth().unwrap();
+        auth.allow().unwrap();
+
+        let caches: Vec<_> = sim.responses().iter().map(|resp| resp.cache).collect();
+        assert_eq!(caches, [false, true]);
+
+        assert_eq!(client.reload_policy(|| 42), (42, Ok(())));
+        client.clear_cache().unwrap();
+        assert_eq!(sim.cache_clears(), 2);
+
+        // the reload isn't lost when the clear is throttled
+        sim.set_clear_cache_throttled(true);
+        assert_eq!(client.reload_policy(|| 43), (43, Err(EsError::Throttled)));
+        sim.set_clear_cache_throttled(false);
+        client.clear_cache().unwrap();
+        assert_eq!(sim.cache_clears(), 3);
+    }
+
+    #[test]
+    fn test_auth_deadline() {
+        let (sim, mut client) = client();
+        client.add_event(EsEventType::AuthOpen).subscribe().unwrap();
+        sim.set_deadline(Duration::from_secs(5));
+
+        let before = Instant::now();
+        sim.emit(EsActionType::Auth, EsEventType::AuthOpen);
+        let auth = client.recv_msg().unwrap().take_auth().unwrap();
+        assert!(auth.deadline() >= before + Duration::from_secs(5));
+        assert!(auth.deadline() <= Instant::now() + Duration::from_secs(5));
+        auth.allow().unwrap();
+    }
+
+    #[test]
+    fn test_watchdog_answers_before_deadline() {
+        let (sim, mut client) = client();
+        client
+            .add_event(EsEventType::AuthUnlink)
+            .subscribe()
+            .unwrap();
+        sim.set_deadline(Duration::from_millis(400));
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let tx = Mutex::new(tx);
+        client
+            .start_auth_watchdog(
+                Duration::from_millis(300),
+                AuthDecision::Deny,
+                Some(Box::new(move |forced| {
+                    _ = tx.lock().unwrap().send(forced.clone());
+                })),
+            )
+            .unwrap();
+
+        sim.emit(EsActionType::Auth, EsEventType::AuthUnlink);
+        let answered = client.recv_msg().unwrap().take_auth().unwrap();
+        answered.allow().unwrap();
+
+        let seq_num = sim.emit(EsActionType::Auth, EsEventType::AuthUnlink);
+        let slow = client.recv_msg().unwrap().take_auth().unwrap();
+        let forced = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(Instant::now() < slow.deadline());
+        assert_eq!(Some(forced.seq_num), seq_num);
+        assert_eq!(forced.decision, AuthDecision::Deny);
+        assert_eq!(forced.result, Ok(()));
+        assert_eq!(slow.allow(), Err(EsError::DuplicateResponse));
+
+        assert_eq!(client.forced_auth_responses(), 1);
+        let results: Vec<_> = sim
+            .responses()
+            .iter()
+            .map(|resp| (resp.result, resp.cache))
+            .collect();
+        assert_eq!(
+            results,
+            [(SimResult::Allow, true), (SimResult::Deny, false)]
+        );
+        assert!(rx.try_recv().is_err());
+
+        client.stop_auth_watchdog();
+        sim.emit(EsActionType::Auth, EsEventType::AuthUnlink);
+        let _unanswered = client.recv_msg().unwrap().take_auth().unwrap();
+        std::thread::sleep(Duration::from_millis(500));
+        assert_eq!(client.forced_auth_responses(), 1);
+    }
+
+    #[test]
+    fn test_notify_has_no_auth_request() {
+        let (sim, mut client) = client();
+        client
+            .add_event(EsEventType::NotifyExit)
+            .subscribe()
+            .unwrap();
+
+        sim.emit(EsActionType::Notify, EsEventType::NotifyExit);
+        assert!(client.recv_msg().unwrap().take_auth().is_none());
+        assert!(sim.responses().is_empty());
+    }
+
+    #[test]
+    fn test_messages_are_released_on_drop() {
+        let (sim, mut client) = client();
+        client
+            .add_event(EsEventType::NotifyExit)
+            .subscribe()
+            .unwrap();
+
+        sim.emit(EsActionType::Notify, EsEventType::NotifyExit);
+        assert_eq!(sim.retained_messages(), 1);
+        drop(client.recv_msg().unwrap());
+        assert_eq!(sim.retained_messages(), 0);
+    }
+
+    #[test]
+    fn test_muted_process_is_not_delivered() {
+        let (sim, mut client) = client();
+        client
+            .add_event(EsEventType::NotifyExit)
+            .subscribe()
+            .unwrap();
+
+        sim.emit(EsActionType::Notify, EsEventType::NotifyExit);
+        let msg = client.recv_msg().unwrap();
+        msg.process.as_ref().unwrap().mute(&client).unwrap();
+
+        assert!(sim
+            .emit(EsActionType::Notify, EsEventType::NotifyExit)
+            .is_none());
+    }
+
+    #[test]
+    fn test_process_events_muting() {
+        let (sim, mut client) = client();
+        client
+            .add_event(EsEventType::NotifyExit)
+            .add_event(EsEventType::NotifyOpen)
+            .subscribe()
+            .unwrap();
+
+        sim.emit(EsActionType::Notify, EsEventType::NotifyExit);
+        let msg = client.recv_msg().unwrap();
+        let token = msg.process.as_ref().unwrap().audit_token;
+        client
+            .mute_process_events(token, &[EsEventType::NotifyExit])
+            .unwrap();
+        assert!(sim
+            .emit(EsActionType::Notify, EsEventType::NotifyExit)
+            .is_none());
+        assert!(sim
+            .emit(EsActionType::Notify, EsEventType::NotifyOpen)
+            .is_some());
+        assert_eq!(
+            client.muted_processes_events().unwrap(),
+            [(token, vec![EsEventType::NotifyExit])]
+        );
+
+        client
+            .unmute_process_events(token, &[EsEventType::NotifyExit])
+            .unwrap();
+        assert!(client.muted_processes_events().unwrap().is_empty());
+        assert!(sim
+            .emit(EsActionType::Notify, EsEventType::NotifyExit)
+            .is_some());
+
+        client.mute_process(token).unwrap();
+        assert!(sim
+            .emit(EsActionType::Notify, EsEventType::NotifyOpen)
+            .is_none());
+        client.unmute_process(token).unwrap();
+        assert!(sim
+            .emit(EsActionType::Notify, EsEventType::NotifyOpen)
+            .is_some());
+    }
+
+    #[test]
+    fn test_muted_path_is_not_delivered() {
+        let (sim, mut client) = client();
+        client
+            .add_event(EsEventType::NotifyExit)
+            .subscribe()
+            .unwrap();
+        let exe = std::env::current_exe().unwrap();
+
+        client
+            .mute_path(exe.parent().unwrap(), EsMutePath::Prefix)
+            .unwrap();
+        assert!(sim
+            .emit(EsActionType::Notify, EsEventType::NotifyExit)
+            .is_none());
+
+        client.unmute_all_paths().unwrap();
+        assert!(sim
+            .emit(EsActionType::Notify, EsEventType::NotifyExit)
+            .is_some());
+    }
+
+    #[test]
+    fn test_muted_paths_inventory() {
+        let (_sim, client) = client();
+        let docs = Path::new("/Users/foo/Documents");
+
+        client
+            .mute_path_events(
+                docs,
+                EsMutePath::TargetPrefix,
+                &[EsEventType::NotifyOpen, EsEventType::NotifyWrite],
+            )
+            .unwrap();
+        client
+            .mute_path(Path::new("/usr/bin/tar"), EsMutePath::Literal)
+            .unwrap();
+        client
+            .unmute_path_events(docs, EsMutePath::TargetPrefix, &[EsEventType::NotifyOpen])
+            .unwrap();
+        assert_eq!(
+            client.mute_path(Path::new("/tmp/a\0b"), EsMutePath::Prefix),
+            Err(EsError::InvalidArgument)
+        );
+
+        let muted = client.muted_paths_events().unwrap();
+        assert_eq!(muted.len(), 2);
+        assert_eq!(muted[0].path, docs);
+        assert_eq!(muted[0].ty, EsMutePath::TargetPrefix);
+        assert_eq!(muted[0].events, [EsEventType::NotifyWrite]);
+        assert_eq!(muted[1].ty, EsMutePath::Literal);
+
+        client.unmute_all_target_paths().unwrap();
+        let muted = client.muted_paths_events().unwrap();
+        assert_eq!(muted.len(), 1);
+        assert_eq!(muted[0].path, Path::new("/usr/bin/tar"));
+        // types from newer systems are listed too
+        let newer = EsMutePath::Unknown(9);
+        client.mute_path(Path::new("/opt"), newer).unwrap();
+        let muted = client.muted_paths_events().unwrap();
+        assert_eq!(muted.len(), 2);
+        assert_eq!(muted[1].ty, newer);
+    }
+
+    #[test]
+    fn test_non_utf8_paths() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let (sim, mut client) = client();
+        client
+            .add_event(EsEventType::NotifyOpen)
+            .subscribe()
+            .unwrap();
+        let latin1 = Path::new(OsStr::from_bytes(b"/tmp/caf\xe9"));
+        let other = Path::new(OsStr::from_bytes(b"/tmp/caf\xe8"));
+
+        client.mute_path(latin1, EsMutePath::TargetLiteral).unwrap();
+        assert_eq!(client.muted_paths_events().unwrap()[0].path, latin1);
+        assert!(sim
+            .emit_file(EsActionType::Notify, EsEventType::NotifyOpen, latin1)
+            .is_none());
+
+        sim.emit_file(EsActionType::Notify, EsEventType::NotifyOpen, other)
+            .unwrap();
+        let msg = client.recv_msg().unwrap();
+        let Some(EsEventData::NotifyOpen(open)) = &msg.event_data else {
+            panic!("unexpected event data {:?}", msg.event_data);
+        };
+        assert_eq!(open.file.path, other.to_string_lossy());
+        assert_eq!(msg.copy().unwrap().message_ref().target_path(), Some(other));
+    }
+
+    #[test]
+    fn test_inverted_muting() {
+        let (sim, mut client) = client();
+        client
+            .add_event(EsEventType::NotifyExit)
+            .subscribe()
+            .unwrap();
+        let exe = std::env::current_exe().unwrap();
+
+        assert_eq!(client.muting_inverted(EsMuteInversion::Path), Ok(false));
+        client.invert_muting(EsMuteInversion::Path).unwrap();
+        assert_eq!(client.muting_inverted(EsMuteInversion::Path), Ok(true));
+        assert!(sim
+            .emit(EsActionType::Notify, EsEventType::NotifyExit)
+            .is_none());
+
+        client.mute_path(&exe, EsMutePath::Literal).unwrap();
+        assert!(sim
+            .emit(EsActionType::Notify, EsEventType::NotifyExit)
+            .is_some());
+
+        client.invert_muting(EsMuteInversion::Path).unwrap();
+        assert_eq!(client.muting_inverted(EsMuteInversion::Path), Ok(false));
+        assert!(sim
+            .emit(EsActionType::Notify, EsEventType::NotifyExit)
+            .is_none());
+    }
+
+    #[test]
+    fn test_event_payload_is_decoded() {
+        let (sim, mut client) = client();
+        client
+            .add_event(EsEventType::AuthUnlink)
+            .add_event(EsEventType::NotifyOpen)
+            .add_event(EsEventType::NotifyExit)
+            .subscribe()
+            .unwrap();
+
+        sim.emit_file(
+            EsActionType::Auth,
+            EsEventType::AuthUnlink,
+            Path::new("/tmp/victim"),
+        );
+        match &client.recv_msg().unwrap().event_data {
+            Some(EsEventData::AuthUnlink(unlink)) => {
+                assert_eq!(unlink.target.path, "/tmp/victim");
+                assert_eq!(unlink.parent_dir.path, "/tmp");
+            }
+            other => panic!("unexpected payload {other:?}"),
+        }
+
+        // payloads that need a target decode to nothing without one
+        sim.emit(EsActionType::Notify, EsEventType::NotifyOpen);
+        assert!(client.recv_msg().unwrap().event_data.is_none());
+        sim.emit(EsActionType::Notify, EsEventType::NotifyExit);
+        assert!(matches!(
+            client.recv_msg().unwrap().event_data,
+            Some(EsEventData::NotifyExit(_))
+        ));
+
+        client
+            .mute_path(Path::new("/tmp"), EsMutePath::TargetPrefix)
+            .unwrap();
+        let target = Path::new("/tmp/victim");
+        assert!(sim
+            .emit_file(EsActionType::Notify, EsEventType::NotifyOpen, target)
+            .is_none());
+        assert!(sim
+            .emit_file(
+                EsActionType::Notify,
+                EsEventType::NotifyOpen,
+                Path::new("/etc/hosts")
+            )
+            .is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "retained a message that wasn't delivered")]
+    fn test_retaining_a_copy_panics() {
+        let (sim, mut client) = client();
+        client
+            .add_event(EsEventType::NotifyExit)
+            .subscribe()
+            .unwrap();
+        sim.emit(EsActionType::Notify, EsEventType::NotifyExit);
+        let copy = client.recv_msg().unwrap().copy().unwrap();
+
+        sim.retain_message(copy.raw());
+    }
+
+    #[test]
+    fn test_drop_disconnects_client() {
+        let (sim, client) = client();
+        assert!(sim.is_connected());
+        drop(client);
+        assert!(!sim.is_connected());
+    }
+}
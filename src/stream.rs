@@ -0,0 +1,144 @@
+//! Receiving messages from async code.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use crate::{EsClient, EsError, EsMessage};
+
+/// Future returned by [EsClient::recv].
+///
+/// It holds no message while pending, dropping it never loses one.
+#[must_use = "futures do nothing unless polled"]
+pub struct Recv<'a> {
+    pub(crate) client: &'a EsClient,
+}
+
+impl Future for Recv<'_> {
+    type Output = Result<EsMessage, EsError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.client.poll_recv(cx)
+    }
+}
+
+/// Stream of the messages of a client, returned by [EsClient::messages]. Ends once the client is
+/// disconnected.
+#[cfg(feature = "async")]
+#[must_use = "streams do nothing unless polled"]
+pub struct EsMessageStream<'a> {
+    pub(crate) client: &'a EsClient,
+}
+
+#[cfg(feature = "async")]
+impl futures_core::Stream for EsMessageStream<'_> {
+    type Item = EsMessage;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.client.poll_recv(cx).map(Result::ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::{Wake, Waker};
+
+    use super::*;
+    use crate::{EsActionType, EsEventType, SimulatedBackend};
+
+    #[derive(Default)]
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn client() -> (Arc<SimulatedBackend>, EsClient) {
+        let sim = Arc::new(SimulatedBackend::new());
+        let mut client = EsClient::with_backend(sim.clone()).unwrap();
+        client
+            .add_event(EsEventType::NotifyExit)
+            .subscribe()
+            .unwrap();
+        (sim, client)
+    }
+
+    #[test]
+    fn test_pending_recv_is_woken() {
+        let (sim, client) = client();
+        let counter = Arc::new(CountingWaker::default());
+        let waker = Waker::from(counter.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let mut recv = client.recv();
+        assert!(Pin::new(&mut recv).poll(&mut cx).is_pending());
+        assert!(Pin::new(&mut recv).poll(&mut cx).is_pending());
+
+        let seq_num = sim.emit(EsActionType::Notify, EsEventType::NotifyExit);
+        assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+        match Pin::new(&mut recv).poll(&mut cx) {
+            Poll::Ready(Ok(msg)) => assert_eq!(Some(msg.seq_num), seq_num),
+            poll => panic!("unexpected {:?}", poll),
+        }
+    }
+
+    #[test]
+    fn test_dropped_recv_loses_no_message() {
+        let (sim, client) = client();
+        let counter = Arc::new(CountingWaker::default());
+        let waker = Waker::from(counter.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let mut recv = client.recv();
+        assert!(Pin::new(&mut recv).poll(&mut cx).is_pending());
+        drop(recv);
+
+        let seq_num = sim.emit(EsActionType::Notify, EsEventType::NotifyExit);
+        let msg = client.try_recv_msg().unwrap().unwrap();
+        assert_eq!(Some(msg.seq_num), seq_num);
+    }
+
+    #[test]
+    fn test_disconnect_wakes_pending_recv() {
+        let (_sim, mut client) = client();
+        let counter = Arc::new(CountingWaker::default());
+        let waker = Waker::from(counter.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        assert!(client.poll_recv(&mut cx).is_pending());
+        client.delete().unwrap();
+        assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+        assert!(matches!(
+            client.poll_recv(&mut cx),
+            Poll::Ready(Err(EsError::Disconnected))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "async")]
+    fn test_stream_yields_messages() {
+        use futures::StreamExt;
+
+        let (sim, client) = client();
+        let emitter = std::thread::spawn(move || {
+            for _ in 0..3 {
+                std::thread::sleep(std::time::Duration::from_millis(10));
+                sim.emit(EsActionType::Notify, EsEventType::NotifyExit);
+            }
+            sim
+        });
+
+        let seq_nums: Vec<_> =
+            futures::executor::block_on(client.messages().take(3).map(|msg| msg.seq_num).collect());
+        assert_eq!(seq_nums, [0, 1, 2]);
+
+        let sim = emitter.join().unwrap();
+        sim.emit(EsActionType::Notify, EsEventType::NotifyExit);
+        let msg = futures::executor::block_on(client.recv()).unwrap();
+        assert_eq!(msg.seq_num, 3);
+    }
+}
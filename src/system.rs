@@ -0,0 +1,358 @@
+//! [EsBackend] implementation on top of libEndpointSecurity, only available on macOS.
+
+use std::ffi::{CStr, CString};
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::time::{Duration, Instant};
+
+use block::ConcreteBlock;
+
+use crate::backend::{EsBackend, EsHandler};
+use crate::sys;
+
+/// Backend that forwards every call to libEndpointSecurity.
+#[derive(Debug, Default)]
+pub struct SystemBackend {
+    client: AtomicPtr<sys::es_client_t>,
+}
+
+impl SystemBackend {
+    fn client(&self) -> *mut sys::es_client_t {
+        self.client.load(Ordering::Acquire)
+    }
+}
+
+impl EsBackend for SystemBackend {
+    fn new_client(&self, handler: EsHandler) -> sys::es_new_client_result_t {
+        let mut client: *mut sys::es_client_t = std::ptr::null_mut();
+
+        let handler = ConcreteBlock::new(
+            move |_c: *mut sys::es_client_t, msg: *const sys::es_message_t| {
+                let Some(message) = (unsafe { msg.as_ref() }) else {
+                    println!("Failed to get message reference");
+                    return;
+                };
+                handler(message);
+            },
+        )
+        .copy();
+
+        let res = unsafe {
+            sys::es_new_client(
+                &mut client as _,
+                &*handler as *const block::Block<_, _> as *mut std::ffi::c_void,
+            )
+        };
+        self.client.store(client, Ordering::Release);
+        res
+    }
+
+    fn delete_client(&self) -> sys::es_return_t {
+        unsafe { sys::es_delete_client(self.client()) }
+    }
+
+    fn subscribe(&self, events: &[sys::es_event_type_t]) -> sys::es_return_t {
+        unsafe { sys::es_subscribe(self.client(), events.as_ptr(), events.len() as u32) }
+    }
+
+    fn unsubscribe(&self, events: &[sys::es_event_type_t]) -> sys::es_return_t {
+        unsafe { sys::es_unsubscribe(self.client(), events.as_ptr(), events.len() as u32) }
+    }
+
+    fn unsubscribe_all(&self) -> sys::es_return_t {
+        unsafe { sys::es_unsubscribe_all(self.client()) }
+    }
+
+    fn subscriptions(&self) -> Option<Vec<sys::es_event_type_t>> {
+        let mut count = 0;
+        let mut eves: *mut sys::es_event_type_t = core::ptr::null_mut();
+        if unsafe { sys::es_subscriptions(self.client(), &mut count, &mut eves) } != 0 {
+            None
+        } else {
+            let events = unsafe { std::slice::from_raw_parts(eves, count) }.to_vec();
+
+            // im not sure if this is the correct way to free
+            extern "C" {
+                fn free(ptr: *mut std::ffi::c_void);
+            }
+            unsafe { free(eves as _) };
+
+            Some(events)
+        }
+    }
+
+    fn respond_auth_result(
+        &self,
+        message: &sys::es_message_t,
+        result: sys::es_auth_result_t,
+        cache: bool,
+    ) -> sys::es_respond_result_t {
+        unsafe { sys::es_respond_auth_result(self.client(), message, result, cache) }
+    }
+
+    fn respond_flags_result(
+        &self,
+        message: &sys::es_message_t,
+        flags: u32,
+        cache: bool,
+    ) -> sys::es_respond_result_t {
+        unsafe { sys::es_respond_flags_result(self.client(), message, flags, cache) }
+    }
+
+    fn mach_time_to_instant(&self, mach_time: u64) -> Instant {
+        #[repr(C)]
+        #[derive(Default)]
+        struct MachTimebaseInfo {
+            numer: u32,
+            denom: u32,
+        }
+
+        extern "C" {
+            fn mach_absolute_time() -> u64;
+            fn mach_timebase_info(info: *mut MachTimebaseInfo) -> i32;
+        }
+
+        let mut timebase = MachTimebaseInfo::default();
+        let (now, now_mach) = unsafe {
+            mach_timebase_info(&mut timebase);
+            (Instant::now(), mach_absolute_time())
+        };
+        let to_nanos = |ticks: u64| {
+            (ticks as u128 * timebase.numer as u128 / timebase.denom.max(1) as u128) as u64
+        };
+
+        if mach_time >= now_mach {
+            now + Duration::from_nanos(to_nanos(mach_time - now_mach))
+        } else {
+            now.checked_sub(Duration::from_nanos(to_nanos(now_mach - mach_time)))
+                .unwrap_or(now)
+        }
+    }
+
+    fn clear_cache(&self) -> sys::es_clear_cache_result_t {
+        unsafe { sys::es_clear_cache(self.client()) }
+    }
+
+    fn mute_process(&self, audit_token: &sys::audit_token_t) -> sys::es_return_t {
+        unsafe { sys::es_mute_process(self.client(), audit_token) }
+    }
+
+    fn mute_process_events(
+        &self,
+        audit_token: &sys::audit_token_t,
+        events: &[sys::es_event_type_t],
+    ) -> sys::es_return_t {
+        unsafe {
+            sys::es_mute_process_events(self.client(), audit_token, events.as_ptr(), events.len())
+        }
+    }
+
+    fn unmute_process(&self, audit_token: &sys::audit_token_t) -> sys::es_return_t {
+        unsafe { sys::es_unmute_process(self.client(), audit_token) }
+    }
+
+    fn unmute_process_events(
+        &self,
+        audit_token: &sys::audit_token_t,
+        events: &[sys::es_event_type_t],
+    ) -> sys::es_return_t {
+        unsafe {
+            sys::es_unmute_process_events(self.client(), audit_token, events.as_ptr(), events.len())
+        }
+    }
+
+    fn muted_processes_events(
+        &self,
+    ) -> Option<Vec<(sys::audit_token_t, Vec<sys::es_event_type_t>)>> {
+        let mut muted: *mut sys::es_muted_processes_t = std::ptr::null_mut();
+        if unsafe { sys::es_muted_processes_events(self.client(), &mut muted) } != 0 {
+            return None;
+        }
+        let list = unsafe { muted.as_ref() }?;
+
+        let processes = if list.count == 0 {
+            vec![]
+        } else {
+            unsafe { std::slice::from_raw_parts(list.processes, list.count) }
+                .iter()
+                .map(|process| {
+                    let events = if process.event_count == 0 {
+                        vec![]
+                    } else {
+                        unsafe { std::slice::from_raw_parts(process.events, process.event_count) }
+                            .to_vec()
+                    };
+                    (process.audit_token, events)
+                })
+                .collect()
+        };
+        unsafe { sys::es_release_muted_processes(muted) };
+
+        Some(processes)
+    }
+
+    fn mute_path(&self, path: &CStr, ty: sys::es_mute_path_type_t) -> sys::es_return_t {
+        unsafe { sys::es_mute_path(self.client(), path.as_ptr(), ty) }
+    }
+
+    fn mute_path_events(
+        &self,
+        path: &CStr,
+        ty: sys::es_mute_path_type_t,
+        events: &[sys::es_event_type_t],
+    ) -> sys::es_return_t {
+        unsafe {
+            sys::es_mute_path_events(
+                self.client(),
+                path.as_ptr(),
+                ty,
+                events.as_ptr(),
+                events.len(),
+            )
+        }
+    }
+
+    fn unmute_path(&self, path: &CStr, ty: sys::es_mute_path_type_t) -> sys::es_return_t {
+        unsafe { sys::es_unmute_path(self.client(), path.as_ptr(), ty) }
+    }
+
+    fn unmute_path_events(
+        &self,
+        path: &CStr,
+        ty: sys::es_mute_path_type_t,
+        events: &[sys::es_event_type_t],
+    ) -> sys::es_return_t {
+        unsafe {
+            sys::es_unmute_path_events(
+                self.client(),
+                path.as_ptr(),
+                ty,
+                events.as_ptr(),
+                events.len(),
+            )
+        }
+    }
+
+    fn unmute_all_paths(&self) -> sys::es_return_t {
+        unsafe { sys::es_unmute_all_paths(self.client()) }
+    }
+
+    fn unmute_all_target_paths(&self) -> sys::es_return_t {
+        unsafe { sys::es_unmute_all_target_paths(self.client()) }
+    }
+
+    fn muted_paths_events(
+        &self,
+    ) -> Option<Vec<(CString, sys::es_mute_path_type_t, Vec<sys::es_event_type_t>)>> {
+        let mut muted: *mut sys::es_muted_paths_t = std::ptr::null_mut();
+        if unsafe { sys::es_muted_paths_events(self.client(), &mut muted) } != 0 {
+            return None;
+        }
+        let list = unsafe { muted.as_ref() }?;
+
+        let paths = if list.count == 0 {
+            vec![]
+        } else {
+            unsafe { std::slice::from_raw_parts(list.paths, list.count) }
+                .iter()
+                .map(|muted_path| {
+                    let path = unsafe { CStr::from_ptr(muted_path.path.data) }.to_owned();
+                    let events = if muted_path.event_count == 0 {
+                        vec![]
+                    } else {
+                        unsafe {
+                            std::slice::from_raw_parts(muted_path.events, muted_path.event_count)
+                        }
+                        .to_vec()
+                    };
+                    (path, muted_path.type_, events)
+                })
+                .collect()
+        };
+        unsafe { sys::es_release_muted_paths(muted) };
+
+        Some(paths)
+    }
+
+    fn invert_muting(&self, ty: sys::es_mute_inversion_type_t) -> sys::es_return_t {
+        unsafe { sys::es_invert_muting(self.client(), ty) }
+    }
+
+    fn muting_inverted(&self, ty: sys::es_mute_inversion_type_t) -> sys::es_mute_inverted_return_t {
+        unsafe { sys::es_muting_inverted(self.client(), ty) }
+    }
+
+    fn exec_args(&self, event: &sys::es_event_exec_t) -> Vec<sys::es_string_token_t> {
+        let count = unsafe { sys::es_exec_arg_count(event) };
+        (0..count)
+            .map(|idx| unsafe { sys::es_exec_arg(event, idx) })
+            .collect()
+    }
+
+    fn exec_envs(&self, event: &sys::es_event_exec_t) -> Vec<sys::es_string_token_t> {
+        let count = unsafe { sys::es_exec_env_count(event) };
+        (0..count)
+            .map(|idx| unsafe { sys::es_exec_env(event, idx) })
+            .collect()
+    }
+
+    fn exec_fds<'a>(&self, event: &'a sys::es_event_exec_t) -> Vec<&'a sys::es_fd_t> {
+        let count = unsafe { sys::es_exec_fd_count(event) };
+        (0..count)
+            .filter_map(|idx| unsafe { sys::es_exec_fd(event, idx).as_ref() })
+            .collect()
+    }
+
+    fn acl_entry_count(&self, event: &sys::es_event_setacl_t) -> usize {
+        let acl = unsafe { event.acl.set };
+        if acl.is_null() {
+            return 0;
+        }
+
+        let mut entry: sys::acl_entry_t = std::ptr::null_mut();
+        let mut entry_id = sys::acl_entry_id_t_ACL_FIRST_ENTRY;
+        let mut count = 0;
+        while unsafe { sys::acl_get_entry(acl, entry_id, &mut entry) } == 0 {
+            count += 1;
+            entry_id = sys::acl_entry_id_t_ACL_NEXT_ENTRY;
+        }
+        count
+    }
+
+    fn acl_text(&self, event: &sys::es_event_setacl_t) -> Option<String> {
+        let acl = unsafe { event.acl.set };
+        if acl.is_null() {
+            return None;
+        }
+
+        let text = unsafe { sys::acl_to_text(acl, std::ptr::null_mut()) };
+        if text.is_null() {
+            return None;
+        }
+        let string = unsafe { CStr::from_ptr(text) }
+            .to_string_lossy()
+            .into_owned();
+        unsafe { sys::acl_free(text.cast()) };
+        Some(string)
+    }
+
+    fn retain_message(&self, message: &sys::es_message_t) {
+        unsafe { sys::es_retain_message(message) }
+    }
+
+    fn release_message(&self, message: &sys::es_message_t) {
+        unsafe { sys::es_release_message(message) }
+    }
+
+    fn copy_message(&self, message: &sys::es_message_t) -> Option<NonNull<sys::es_message_t>> {
+        NonNull::new(unsafe { sys::es_copy_message(message) })
+    }
+
+    fn free_message(&self, message: &sys::es_message_t) {
+        unsafe { sys::es_free_message(message as *const _ as *mut _) }
+    }
+
+    fn message_size(&self, message: &sys::es_message_t) -> usize {
+        unsafe { sys::es_message_size(message) }
+    }
+}
@@ -0,0 +1,164 @@
+//! Answering auth events that are about to miss their deadline.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::atomic::{self, AtomicU64};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, Weak};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::auth::{AuthDecision, PendingAuth};
+use crate::{EsError, EsEventType};
+
+/// A response the watchdog sent on behalf of the client
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ForcedAuthResponse {
+    pub event: EsEventType,
+    /// sequence number of the message that was answered
+    pub seq_num: u64,
+    pub decision: AuthDecision,
+    /// outcome of sending the response
+    pub result: Result<(), EsError>,
+}
+
+/// Called from the watchdog thread for every response it forces
+pub type ForcedAuthCallback = Box<dyn Fn(&ForcedAuthResponse) + Send + Sync>;
+
+struct Entry {
+    fire_at: Instant,
+    auth: Weak<PendingAuth>,
+}
+
+// ordered so that the `BinaryHeap` pops the earliest entry first
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.fire_at.cmp(&self.fire_at)
+    }
+}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.fire_at == other.fire_at
+    }
+}
+
+impl Eq for Entry {}
+
+#[derive(Default)]
+struct WatchdogState {
+    pending: BinaryHeap<Entry>,
+    stopped: bool,
+}
+
+/// Tracks the auth messages of a client and answers the ones still pending `margin` before
+/// their deadline.
+pub(crate) struct Watchdog {
+    margin: Duration,
+    decision: AuthDecision,
+    callback: Option<ForcedAuthCallback>,
+    forced: Arc<AtomicU64>,
+    state: Mutex<WatchdogState>,
+    wakeup: Condvar,
+}
+
+impl Watchdog {
+    pub(crate) fn spawn(
+        margin: Duration,
+        decision: AuthDecision,
+        callback: Option<ForcedAuthCallback>,
+        forced: Arc<AtomicU64>,
+    ) -> Result<(Arc<Self>, JoinHandle<()>), EsError> {
+        let watchdog = Arc::new(Self {
+            margin,
+            decision,
+            callback,
+            forced,
+            state: Mutex::default(),
+            wakeup: Condvar::new(),
+        });
+
+        let thread_watchdog = watchdog.clone();
+        let handle = std::thread::Builder::new()
+            .name("es-auth-watchdog".into())
+            .spawn(move || thread_watchdog.run())
+            .map_err(|_| EsError::ThreadSpawn)?;
+
+        Ok((watchdog, handle))
+    }
+
+    fn state(&self) -> MutexGuard<'_, WatchdogState> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    pub(crate) fn track(&self, auth: &Arc<PendingAuth>) {
+        let fire_at = auth
+            .deadline()
+            .checked_sub(self.margin)
+            .unwrap_or_else(Instant::now);
+
+        self.state().pending.push(Entry {
+            fire_at,
+            auth: Arc::downgrade(auth),
+        });
+        self.wakeup.notify_one();
+    }
+
+    /// Make the thread exit, messages it still tracks are left to their [AuthRequest](crate::AuthRequest)
+    pub(crate) fn stop(&self) {
+        self.state().stopped = true;
+        self.wakeup.notify_one();
+    }
+
+    fn run(&self) {
+        let mut state = self.state();
+        while !state.stopped {
+            let now = Instant::now();
+            let next = state.pending.peek().map(|entry| entry.fire_at);
+
+            state = match next {
+                None => self
+                    .wakeup
+                    .wait(state)
+                    .unwrap_or_else(|err| err.into_inner()),
+                Some(fire_at) if fire_at > now => self
+                    .wakeup
+                    .wait_timeout(state, fire_at - now)
+                    .map(|(state, _)| state)
+                    .unwrap_or_else(|err| err.into_inner().0),
+                Some(_) => {
+                    let entry = state.pending.pop();
+                    drop(state);
+                    if let Some(auth) = entry.and_then(|entry| entry.auth.upgrade()) {
+                        self.force(&auth);
+                    }
+                    self.state()
+                }
+            };
+        }
+    }
+
+    fn force(&self, auth: &PendingAuth) {
+        // answered by the client in the meantime
+        if !auth.claim() {
+            return;
+        }
+
+        let result = auth.send(self.decision, false);
+        self.forced.fetch_add(1, atomic::Ordering::Relaxed);
+
+        if let Some(callback) = &self.callback {
+            callback(&ForcedAuthResponse {
+                event: auth.event(),
+                seq_num: auth.seq_num(),
+                decision: self.decision,
+                result,
+            });
+        }
+    }
+}